@@ -0,0 +1,329 @@
+//! Audio files uploaded along with `~play`, downloaded to a cache directory
+//! for as long as their tracks are around.
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex as StdMutex, MutexGuard, PoisonError},
+};
+
+use serenity::{
+    async_trait,
+    client::Context,
+    model::channel::Attachment,
+    model::id::GuildId,
+    prelude::{Mutex, TypeMapKey},
+};
+use songbird::{
+    input::{Input, Metadata},
+    tracks::TrackHandle,
+};
+
+use crate::{
+    config,
+    error::{Result, SourceError},
+    lazy, library,
+    sources::{Resolved, Song, SourceResolver},
+};
+
+/// Whether `attachment` looks like something ffmpeg can play.
+pub fn is_audio(attachment: &Attachment) -> bool {
+    match &attachment.content_type {
+        Some(content_type) => content_type.starts_with("audio/"),
+        None => library::is_audio(Path::new(&attachment.filename)),
+    }
+}
+
+/// Extension of downloads whose upload didn't have an audio one, ffmpeg
+/// goes by the contents anyway.
+const FALLBACK_EXTENSION: &str = "audio";
+
+/// Cached files something still needs, once for every [`CachedFile`].
+static IN_USE: StdMutex<Vec<PathBuf>> = StdMutex::new(Vec::new());
+
+fn in_use() -> MutexGuard<'static, Vec<PathBuf>> {
+    IN_USE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keeps a cached attachment around, the file is deleted once the last one
+/// for it is dropped. Every track made from it holds one in its typemap, so
+/// it goes whether the track ends, is stopped or removed, or never got queued.
+pub struct CachedFile {
+    path: PathBuf,
+}
+
+impl CachedFile {
+    fn new(path: PathBuf) -> Self {
+        in_use().push(path.clone());
+
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for CachedFile {
+    fn drop(&mut self) {
+        let mut in_use = in_use();
+        if let Some(index) = in_use.iter().position(|path| *path == self.path) {
+            in_use.swap_remove(index);
+        }
+        // a looping queue plays it again from the same file
+        if in_use.contains(&self.path) {
+            return;
+        }
+        drop(in_use);
+
+        let path = self.path.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(move || remove(&path));
+            }
+            Err(_) => remove(&path),
+        }
+    }
+}
+
+impl TypeMapKey for CachedFile {
+    type Value = CachedFile;
+}
+
+fn remove(path: &Path) {
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(why) if why.kind() == io::ErrorKind::NotFound => {}
+        Err(why) => tracing::warn!("Couldn't remove {}: {:?}", path.display(), why),
+    }
+}
+
+/// Whether `path` is named like the files [`download`] writes, `<id>.<ext>`
+/// with an audio extension. The cache directory is configurable, anything
+/// else in there isn't ours to delete.
+fn is_download(path: &Path) -> bool {
+    let named_by_id = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.parse::<u64>().is_ok());
+    let extension = path.extension().and_then(|ext| ext.to_str());
+
+    named_by_id && (extension == Some(FALLBACK_EXTENSION) || library::is_audio(path))
+}
+
+/// Uploads of the queues set aside for `~restore`, by guild. Their tracks
+/// are gone, these keep the files around until the queue is restored.
+pub struct SetAside;
+
+impl TypeMapKey for SetAside {
+    type Value = Arc<Mutex<HashMap<GuildId, Vec<CachedFile>>>>;
+}
+
+async fn set_aside_files(ctx: &Context) -> Arc<Mutex<HashMap<GuildId, Vec<CachedFile>>>> {
+    ctx.data
+        .read()
+        .await
+        .get::<SetAside>()
+        .cloned()
+        .expect("SetAside placed in at initialisation.")
+}
+
+/// Takes the cached files over from `tracks`, a queue being set aside.
+pub async fn set_aside(ctx: &Context, guild_id: GuildId, tracks: &[TrackHandle]) {
+    let mut files = Vec::new();
+    for track in tracks {
+        if let Some(file) = track.typemap().write().await.remove::<CachedFile>() {
+            files.push(file);
+        }
+    }
+
+    set_aside_files(ctx)
+        .await
+        .lock()
+        .await
+        .insert(guild_id, files);
+}
+
+/// Lets go of the files of the queue `guild_id` set aside, once it's been
+/// restored or discarded.
+pub async fn release(ctx: &Context, guild_id: GuildId) {
+    set_aside_files(ctx).await.lock().await.remove(&guild_id);
+}
+
+/// Deletes the cached file at `path`, unless a track still plays it.
+pub fn forget(path: &Path) {
+    if is_download(path) && is_cached(path) {
+        drop(CachedFile::new(path.to_owned()));
+    }
+}
+
+/// Deletes every download in the cache but the `kept` ones, whatever was
+/// left behind the last time the bot stopped.
+pub fn sweep(kept: &[PathBuf]) {
+    let entries = match fs::read_dir(&config::get().attachment_dir) {
+        Ok(entries) => entries,
+        Err(why) if why.kind() == io::ErrorKind::NotFound => return,
+        Err(why) => {
+            tracing::warn!("Couldn't clean up the attachment cache: {:?}", why);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if is_download(&path) && path.is_file() && !kept.contains(&path) {
+            remove(&path);
+        }
+    }
+}
+
+/// Downloads `attachment` into the cache, it stays there for as long as the
+/// returned file or a track made from it is around.
+pub async fn download(attachment: &Attachment) -> Result<CachedFile> {
+    let config = config::get();
+
+    if !is_audio(attachment) {
+        return Err(SourceError::Rejected(format!(
+            "`{}` isn't an audio file",
+            attachment.filename
+        ))
+        .into());
+    }
+    if attachment.size > config.max_attachment_mb * 1024 * 1024 {
+        return Err(SourceError::Rejected(format!(
+            "`{}` is too big, attachments can be at most {}MB",
+            attachment.filename, config.max_attachment_mb
+        ))
+        .into());
+    }
+
+    let bytes = attachment.download().await?;
+
+    // named by id, whatever the uploader called it can't escape the cache
+    let filename = Path::new(&attachment.filename);
+    let extension = match filename.extension().and_then(|ext| ext.to_str()) {
+        Some(extension) if library::is_audio(filename) => extension,
+        _ => FALLBACK_EXTENSION,
+    };
+    // held before writing, so that a half written file goes too
+    let file = CachedFile::new(
+        config
+            .attachment_dir
+            .join(format!("{}.{}", attachment.id, extension)),
+    );
+
+    let target = file.path.clone();
+    tokio::task::spawn_blocking(move || {
+        fs::create_dir_all(target.parent().expect("Cached files are in a directory."))?;
+        fs::write(&target, bytes)
+    })
+    .await??;
+
+    Ok(file)
+}
+
+/// Whether `path` is a file in the cache, and nowhere else.
+fn is_cached(path: &Path) -> bool {
+    let cache = match config::get().attachment_dir.canonicalize() {
+        Ok(cache) => cache,
+        Err(_) => return false,
+    };
+
+    match path.canonicalize() {
+        Ok(path) => path.starts_with(cache) && path.is_file(),
+        Err(_) => false,
+    }
+}
+
+/// Cached uploads, by their path in the cache. The upload's own file name
+/// comes in as the title of the `known` metadata.
+pub struct AttachmentResolver;
+
+impl AttachmentResolver {
+    pub const NAME: &'static str = "attachment";
+}
+
+#[async_trait]
+impl SourceResolver for AttachmentResolver {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    /// `~play` picks these itself when there are uploads.
+    fn handles(&self, _query: &str) -> bool {
+        false
+    }
+
+    async fn resolve(&self, query: &str, known: Option<Metadata>) -> Result<Resolved> {
+        let path = PathBuf::from(query);
+        // the cached file is named by its id, the upload had a real name
+        let title = known.and_then(|known| known.title).or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+
+        if !is_cached(&path) {
+            return Err(SourceError::NotFound(format!(
+                "`{}` is gone, upload it again",
+                title.unwrap_or_default()
+            ))
+            .into());
+        }
+
+        let (source, buffered) = lazy::ffmpeg(path).await?;
+
+        let input: Input = source.into();
+        let mut metadata = *input.metadata.clone();
+        metadata.title = title;
+
+        Ok(Resolved {
+            input,
+            metadata,
+            buffered,
+            song: Song::new(Self::NAME, query),
+        })
+    }
+
+    async fn track_added(
+        &self,
+        _ctx: &Context,
+        _guild_id: GuildId,
+        track: &TrackHandle,
+        query: &str,
+    ) {
+        let file = CachedFile::new(query.into());
+        track.typemap().write().await.insert::<CachedFile>(file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_downloads_are_swept() {
+        assert!(is_download(Path::new("/tmp/cache/889245337563873300.mp3")));
+        assert!(is_download(Path::new(
+            "/tmp/cache/889245337563873300.audio"
+        )));
+        assert!(is_download(Path::new("889245337563873300.FLAC")));
+        assert!(!is_download(Path::new("/home/someone/notes.mp3")));
+        assert!(!is_download(Path::new("/home/someone/2021.txt")));
+        assert!(!is_download(Path::new("/home/someone/889245337563873300")));
+    }
+
+    #[test]
+    fn cached_files_go_with_the_last_holder() {
+        let path = std::env::temp_dir().join(format!("aoede-cached-{}.mp3", std::process::id()));
+        fs::write(&path, b"").unwrap();
+
+        // a track and its looped copy
+        let first = CachedFile::new(path.clone());
+        let second = CachedFile::new(path.clone());
+
+        drop(first);
+        assert!(path.is_file());
+        drop(second);
+        assert!(!path.exists());
+    }
+}
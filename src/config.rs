@@ -0,0 +1,390 @@
+//! Bot wide configuration, read once at startup from a toml file.
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use serde::Deserialize;
+use serenity::model::gateway::Activity;
+
+/// Where the config is looked for when neither `--config` nor `AOEDE_CONFIG` say otherwise.
+pub const DEFAULT_CONFIG_PATH: &str = "aoede.toml";
+
+/// Loudest volume in percent anyone can ask for.
+pub const MAX_VOLUME: u16 = 200;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The bot token, mutually exclusive with `token_file`.
+    pub token: Option<String>,
+    /// A file holding nothing but the bot token.
+    pub token_file: Option<PathBuf>,
+    /// Prefix for guilds that haven't set their own.
+    pub prefix: String,
+    /// Where the per-guild settings are stored.
+    pub settings_path: PathBuf,
+    /// Directory the queue snapshots are kept in.
+    pub queue_dir: PathBuf,
+    /// Root of the local music library `~local` plays from, `None` turns it off.
+    pub music_dir: Option<PathBuf>,
+    /// Where files uploaded with `~play` are kept until they've played.
+    pub attachment_dir: PathBuf,
+    /// Largest upload `~play` accepts, in MB.
+    pub max_attachment_mb: u64,
+    /// Thumbnail and footer icon of every embed.
+    pub icon: String,
+    /// Embed colour as `[r, g, b]`.
+    pub embed_colour: (u8, u8, u8),
+    pub activity: ActivityConfig,
+    /// Volume in percent for guilds that haven't picked their own.
+    pub default_volume: u16,
+    /// Seconds of audio a song buffers before the queue starts playing it.
+    pub prebuffer_audio_secs: u64,
+    /// How long to wait for that before playing anyway.
+    pub prebuffer_timeout_secs: u64,
+    /// Seconds to stay in a voice channel everyone else left, 0 stays forever.
+    pub empty_channel_timeout_secs: u64,
+    /// Seconds to stay in a voice channel with nothing playing, 0 stays forever.
+    pub idle_timeout_secs: u64,
+    /// How many songs after the current one get started ahead of time.
+    pub preload_ahead: usize,
+    /// Most songs one `~play_playlist` imports, guilds can only lower it.
+    pub max_playlist_size: usize,
+    /// How many results `~search` offers to pick from.
+    pub search_results: usize,
+    /// Seconds `~search` waits for a pick.
+    pub search_timeout_secs: u64,
+    /// Percent of the listeners that have to `voteskip` a song to skip it.
+    pub voteskip_percent: u8,
+    /// Passed to youtube-dl as `--socket-timeout`.
+    pub ytdl_socket_timeout: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            token: None,
+            token_file: None,
+            prefix: "~".into(),
+            settings_path: "aoede_settings.json".into(),
+            queue_dir: "queues".into(),
+            music_dir: None,
+            attachment_dir: env::temp_dir().join("aoede-attachments"),
+            max_attachment_mb: 25,
+            icon: "https://cdn.discordapp.com/avatars/887241846869360641/70525dd8fab9290f78cc7ad2e26728a6.webp".into(),
+            embed_colour: (253, 195, 213),
+            activity: ActivityConfig::default(),
+            default_volume: 100,
+            prebuffer_audio_secs: 3,
+            prebuffer_timeout_secs: 30,
+            empty_channel_timeout_secs: 300,
+            idle_timeout_secs: 600,
+            preload_ahead: 2,
+            max_playlist_size: 100,
+            search_results: 5,
+            search_timeout_secs: 30,
+            voteskip_percent: 50,
+            ytdl_socket_timeout: 15,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActivityConfig {
+    pub kind: ActivityKind,
+    pub text: String,
+}
+
+impl Default for ActivityConfig {
+    fn default() -> Self {
+        Self {
+            kind: ActivityKind::Watching,
+            text: "your mother".into(),
+        }
+    }
+}
+
+impl ActivityConfig {
+    pub fn to_activity(&self) -> Activity {
+        match self.kind {
+            ActivityKind::Playing => Activity::playing(&self.text),
+            ActivityKind::Listening => Activity::listening(&self.text),
+            ActivityKind::Watching => Activity::watching(&self.text),
+            ActivityKind::Competing => Activity::competing(&self.text),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Playing,
+    Listening,
+    Watching,
+    Competing,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(&'static str, String),
+    TokenFile(PathBuf, io::Error),
+    NoToken,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "{} is not valid: {}", path.display(), e),
+            ConfigError::Invalid(key, why) => write!(f, "`{}` {}", key, why),
+            ConfigError::TokenFile(path, e) => {
+                write!(f, "couldn't read token file {}: {}", path.display(), e)
+            }
+            ConfigError::NoToken => write!(
+                f,
+                "no token given, set `token` or `token_file` in the config or DISCORD_TOKEN in the environment"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Finds the config path from `--config <path>`, then `AOEDE_CONFIG`,
+    /// the flag says whether it was picked explicitly.
+    pub fn path_from_args() -> (PathBuf, bool) {
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--config" || arg == "-c" {
+                if let Some(path) = args.next() {
+                    return (path.into(), true);
+                }
+            } else if let Some(path) = arg.strip_prefix("--config=") {
+                return (path.into(), true);
+            }
+        }
+
+        match env::var("AOEDE_CONFIG") {
+            Ok(path) => (path.into(), true),
+            Err(_) => (DEFAULT_CONFIG_PATH.into(), false),
+        }
+    }
+
+    /// Loads and validates the config, a missing file is only fine if
+    /// nobody asked for it explicitly.
+    pub fn load(path: &Path, explicit: bool) -> Result<Self, ConfigError> {
+        let config: Config = match fs::read_to_string(path) {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !explicit => Config::default(),
+            Err(e) => return Err(ConfigError::Read(path.to_path_buf(), e)),
+        };
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.token.is_some() && self.token_file.is_some() {
+            return Err(ConfigError::Invalid(
+                "token",
+                "can't be set together with `token_file`".into(),
+            ));
+        }
+
+        if self.prefix.is_empty()
+            || self.prefix.chars().count() > 5
+            || self.prefix.contains(char::is_whitespace)
+        {
+            return Err(ConfigError::Invalid(
+                "prefix",
+                format!(
+                    "must be 1 to 5 characters without spaces, got {:?}",
+                    self.prefix
+                ),
+            ));
+        }
+
+        if !self.icon.starts_with("http") {
+            return Err(ConfigError::Invalid(
+                "icon",
+                format!("must be an http(s) URL, got {:?}", self.icon),
+            ));
+        }
+
+        if let Some(music_dir) = &self.music_dir {
+            if !music_dir.is_dir() {
+                return Err(ConfigError::Invalid(
+                    "music_dir",
+                    format!("must be an existing directory, got {}", music_dir.display()),
+                ));
+            }
+
+            // uploads get cleaned up from there, the library mustn't be
+            let library = music_dir.canonicalize().ok();
+            if library.is_some() && self.attachment_dir.canonicalize().ok() == library {
+                return Err(ConfigError::Invalid(
+                    "attachment_dir",
+                    "can't be the same directory as `music_dir`".into(),
+                ));
+            }
+        }
+
+        if self.max_attachment_mb == 0 {
+            return Err(ConfigError::Invalid(
+                "max_attachment_mb",
+                "must be at least 1".into(),
+            ));
+        }
+
+        if self.activity.text.is_empty() {
+            return Err(ConfigError::Invalid(
+                "activity.text",
+                "can't be empty".into(),
+            ));
+        }
+
+        if self.default_volume > MAX_VOLUME {
+            return Err(ConfigError::Invalid(
+                "default_volume",
+                format!(
+                    "must be at most {}, got {}",
+                    MAX_VOLUME, self.default_volume
+                ),
+            ));
+        }
+
+        if self.prebuffer_audio_secs > 30 {
+            return Err(ConfigError::Invalid(
+                "prebuffer_audio_secs",
+                format!("must be at most 30, got {}", self.prebuffer_audio_secs),
+            ));
+        }
+
+        if self.prebuffer_timeout_secs == 0 || self.prebuffer_timeout_secs > 300 {
+            return Err(ConfigError::Invalid(
+                "prebuffer_timeout_secs",
+                format!(
+                    "must be between 1 and 300, got {}",
+                    self.prebuffer_timeout_secs
+                ),
+            ));
+        }
+
+        if self.preload_ahead > 10 {
+            return Err(ConfigError::Invalid(
+                "preload_ahead",
+                format!("must be at most 10, got {}", self.preload_ahead),
+            ));
+        }
+
+        if self.max_playlist_size == 0 {
+            return Err(ConfigError::Invalid(
+                "max_playlist_size",
+                "must be at least 1".into(),
+            ));
+        }
+
+        if self.search_results == 0 || self.search_results > 10 {
+            return Err(ConfigError::Invalid(
+                "search_results",
+                format!("must be between 1 and 10, got {}", self.search_results),
+            ));
+        }
+
+        if self.search_timeout_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "search_timeout_secs",
+                "must be at least 1 second".into(),
+            ));
+        }
+
+        if self.voteskip_percent == 0 || self.voteskip_percent > 100 {
+            return Err(ConfigError::Invalid(
+                "voteskip_percent",
+                format!("must be between 1 and 100, got {}", self.voteskip_percent),
+            ));
+        }
+
+        if self.ytdl_socket_timeout == 0 {
+            return Err(ConfigError::Invalid(
+                "ytdl_socket_timeout",
+                "must be at least 1 second".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The token from the config, the token file, or `DISCORD_TOKEN`, in that order.
+    pub fn token(&self) -> Result<String, ConfigError> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
+
+        if let Some(path) = &self.token_file {
+            return fs::read_to_string(path)
+                .map(|token| token.trim().to_string())
+                .map_err(|e| ConfigError::TokenFile(path.clone(), e));
+        }
+
+        env::var("DISCORD_TOKEN").map_err(|_| ConfigError::NoToken)
+    }
+}
+
+/// Makes `config` the one returned by [`get`], only the first call counts.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// The loaded config, panics if called before [`init`].
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("Config loaded at initialisation.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Result<Config, ConfigError> {
+        let config: Config = toml::from_str(toml).map_err(|e| ConfigError::Parse("".into(), e))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(parse("").is_ok());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(matches!(
+            parse("prefix = \"\""),
+            Err(ConfigError::Invalid("prefix", _))
+        ));
+        assert!(matches!(
+            parse("default_volume = 1000"),
+            Err(ConfigError::Invalid("default_volume", _))
+        ));
+        assert!(matches!(parse("unknown = 1"), Err(ConfigError::Parse(..))));
+
+        let dir = env::temp_dir().display().to_string();
+        let same_dirs = format!("music_dir = {:?}\nattachment_dir = {:?}", dir, dir);
+        assert!(matches!(
+            parse(&same_dirs),
+            Err(ConfigError::Invalid("attachment_dir", _))
+        ));
+    }
+}
@@ -0,0 +1,219 @@
+//! Everything that can go wrong in a command, and how it's told to whoever
+//! ran it.
+use std::{error::Error as StdError, fmt, io};
+
+use serenity::Error as SerenityError;
+use songbird::{error::JoinError, input::error::Error as InputError, tracks::TrackError};
+use tokio::task::JoinError as TaskError;
+
+use crate::{embed_colour, icon, Reply};
+
+pub type Result<T, E = AoedeError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum AoedeError {
+    /// A song couldn't be found or started.
+    Source(SourceError),
+    /// The voice connection or the playback didn't cooperate.
+    Voice(VoiceError),
+    /// The caller isn't allowed to do that, the text says why.
+    Permission(String),
+    /// Discord didn't do what we asked.
+    Discord(SerenityError),
+    /// The guild isn't in the cache, discord hasn't told us about it yet.
+    GuildUnavailable,
+    /// Our own files, the settings, the saved queues and such.
+    Io(io::Error),
+    /// Whatever else a command returned.
+    Other(Box<dyn StdError + Send + Sync>),
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    /// youtube-dl or ffmpeg couldn't be started, or gave up.
+    Input(InputError),
+    /// youtube-dl couldn't look something up.
+    Ytdl(youtube_dl::Error),
+    /// A lookup running off the runtime panicked.
+    Task(TaskError),
+    /// There's nothing to play by that name, the text says what was asked for.
+    NotFound(String),
+    /// It's there but won't be played, the text says why.
+    Rejected(String),
+}
+
+#[derive(Debug)]
+pub enum VoiceError {
+    /// Not in a voice channel in this guild.
+    NotConnected,
+    /// The caller isn't in one either, so there's nowhere to join.
+    NoChannel,
+    Join(Box<JoinError>),
+    Track(TrackError),
+}
+
+impl AoedeError {
+    /// What whoever ran the command gets told.
+    pub fn user_message(&self) -> String {
+        match self {
+            _ if self.is_age_restricted() => {
+                "The video you're trying to play is age-restricted.".into()
+            }
+            AoedeError::Source(SourceError::Input(_)) => "Error sourcing ffmpeg".into(),
+            AoedeError::Source(SourceError::Ytdl(_)) => {
+                "youtube-dl couldn't make sense of that".into()
+            }
+            AoedeError::Source(SourceError::Task(_)) => "The lookup crashed".into(),
+            AoedeError::Source(SourceError::NotFound(what))
+            | AoedeError::Source(SourceError::Rejected(what)) => what.clone(),
+            AoedeError::Voice(VoiceError::NotConnected) => {
+                "Not in a voice channel to play in".into()
+            }
+            AoedeError::Voice(VoiceError::NoChannel) => {
+                "Join a voice channel first so I know where to play".into()
+            }
+            AoedeError::Voice(VoiceError::Join(_)) => "Error joining the channel".into(),
+            AoedeError::Voice(VoiceError::Track(TrackError::Finished)) => {
+                "The song is already finished, i beg you, ᵖˡᵉᵃˢᵉ ᵠᵘᵉᵘᵉ ᵃⁿᵒᵗʰᵉʳ ᵒⁿᵉ".into()
+            }
+            AoedeError::Voice(VoiceError::Track(_)) => "Couldn't control the song".into(),
+            AoedeError::Permission(why) => why.clone(),
+            AoedeError::Discord(_) => "Discord didn't cooperate".into(),
+            AoedeError::GuildUnavailable => {
+                "Discord hasn't told me about this server yet, try again in a bit".into()
+            }
+            AoedeError::Io(_) => "Couldn't read or write my files".into(),
+            AoedeError::Other(_) => "Something went wrong".into(),
+        }
+    }
+
+    fn is_age_restricted(&self) -> bool {
+        matches!(
+            self,
+            AoedeError::Source(SourceError::Input(InputError::Json { parsed_text, .. }))
+                if parsed_text == "ERROR: Sign in to confirm your age\n"
+        )
+    }
+
+    /// Whether it's our fault rather than a mistake of the caller's.
+    fn is_internal(&self) -> bool {
+        match self {
+            AoedeError::Source(SourceError::NotFound(_))
+            | AoedeError::Source(SourceError::Rejected(_))
+            | AoedeError::Voice(VoiceError::NotConnected)
+            | AoedeError::Voice(VoiceError::NoChannel)
+            | AoedeError::Voice(VoiceError::Track(TrackError::Finished))
+            | AoedeError::Permission(_) => false,
+            _ => !self.is_age_restricted(),
+        }
+    }
+
+    /// Logs the error under a fresh id, and makes the embed telling the
+    /// caller about it, id included so the log line can be found.
+    pub fn report(&self, command: &str) -> Reply {
+        let id = format!("{:08x}", rand::random::<u32>());
+
+        if self.is_internal() {
+            tracing::error!(error_id = %id, command, "{}", self);
+        } else {
+            tracing::info!(error_id = %id, command, "{}", self);
+        }
+
+        let description = self.user_message();
+        Reply::embed(None, |e| {
+            e.colour(embed_colour())
+                .title(format!("Couldn't run `{}`", command))
+                .description(description)
+                .footer(|f| f.text(format!("Error ID: {}", id)).icon_url(icon()))
+        })
+    }
+}
+
+impl fmt::Display for AoedeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AoedeError::Source(SourceError::Input(e)) => write!(f, "sourcing failed: {:?}", e),
+            AoedeError::Source(SourceError::Ytdl(e)) => write!(f, "youtube-dl failed: {}", e),
+            AoedeError::Source(SourceError::Task(e)) => write!(f, "lookup task failed: {}", e),
+            AoedeError::Source(SourceError::NotFound(what)) => write!(f, "not found: {}", what),
+            AoedeError::Source(SourceError::Rejected(why)) => write!(f, "rejected: {}", why),
+            AoedeError::Voice(VoiceError::NotConnected) => write!(f, "not in a voice channel"),
+            AoedeError::Voice(VoiceError::NoChannel) => {
+                write!(f, "caller isn't in a voice channel")
+            }
+            AoedeError::Voice(VoiceError::Join(e)) => write!(f, "joining failed: {}", e),
+            AoedeError::Voice(VoiceError::Track(e)) => write!(f, "track control failed: {}", e),
+            AoedeError::Permission(why) => write!(f, "not allowed: {}", why),
+            AoedeError::Discord(e) => write!(f, "discord error: {}", e),
+            AoedeError::GuildUnavailable => write!(f, "guild not in the cache"),
+            AoedeError::Io(e) => write!(f, "io error: {}", e),
+            AoedeError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl StdError for AoedeError {}
+
+impl From<SourceError> for AoedeError {
+    fn from(e: SourceError) -> Self {
+        AoedeError::Source(e)
+    }
+}
+
+impl From<VoiceError> for AoedeError {
+    fn from(e: VoiceError) -> Self {
+        AoedeError::Voice(e)
+    }
+}
+
+impl From<InputError> for AoedeError {
+    fn from(e: InputError) -> Self {
+        AoedeError::Source(SourceError::Input(e))
+    }
+}
+
+impl From<youtube_dl::Error> for AoedeError {
+    fn from(e: youtube_dl::Error) -> Self {
+        AoedeError::Source(SourceError::Ytdl(e))
+    }
+}
+
+impl From<TaskError> for AoedeError {
+    fn from(e: TaskError) -> Self {
+        AoedeError::Source(SourceError::Task(e))
+    }
+}
+
+impl From<JoinError> for AoedeError {
+    fn from(e: JoinError) -> Self {
+        AoedeError::Voice(VoiceError::Join(Box::new(e)))
+    }
+}
+
+impl From<TrackError> for AoedeError {
+    fn from(e: TrackError) -> Self {
+        AoedeError::Voice(VoiceError::Track(e))
+    }
+}
+
+impl From<SerenityError> for AoedeError {
+    fn from(e: SerenityError) -> Self {
+        AoedeError::Discord(e)
+    }
+}
+
+impl From<io::Error> for AoedeError {
+    fn from(e: io::Error) -> Self {
+        AoedeError::Io(e)
+    }
+}
+
+impl From<Box<dyn StdError + Send + Sync>> for AoedeError {
+    /// Unwraps our own errors back out of what a command returned.
+    fn from(e: Box<dyn StdError + Send + Sync>) -> Self {
+        match e.downcast::<AoedeError>() {
+            Ok(e) => *e,
+            Err(e) => AoedeError::Other(e),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Leaving voice channels nobody is using, unless the guild wants the bot
+//! around 24/7.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use serenity::{
+    async_trait,
+    client::Context,
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::{Mutex, TypeMapKey},
+};
+use songbird::{Event, EventContext, EventHandler as VoiceEventHandler};
+
+use crate::{attachments, check_msg, config, guild_settings, queue_store, set_queue_looping};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Reason {
+    /// Everyone else left the voice channel.
+    EmptyChannel,
+    /// The queue ran out.
+    Idle,
+}
+
+impl Reason {
+    fn timeout(self) -> Duration {
+        let config = config::get();
+        Duration::from_secs(match self {
+            Reason::EmptyChannel => config.empty_channel_timeout_secs,
+            Reason::Idle => config.idle_timeout_secs,
+        })
+    }
+
+    fn announcement(self, prefix: &str) -> String {
+        match self {
+            Reason::EmptyChannel => format!(
+                "Left the voice channel since everyone else did, use `{}restore` to pick the queue back up",
+                prefix
+            ),
+            Reason::Idle => "Left the voice channel since nothing was playing".into(),
+        }
+    }
+
+    /// Whether leaving for this reason still makes sense.
+    async fn holds(self, ctx: &Context, guild_id: GuildId) -> bool {
+        let manager = songbird::get(ctx)
+            .await
+            .expect("Songbird Voice client placed in at initialisation.")
+            .clone();
+
+        let handler_lock = match manager.get(guild_id) {
+            Some(handler_lock) => handler_lock,
+            None => return false,
+        };
+        let handler = handler_lock.lock().await;
+
+        match self {
+            Reason::EmptyChannel => match handler.current_channel() {
+                Some(channel) => listeners(ctx, guild_id, ChannelId(channel.0))
+                    .await
+                    .is_empty(),
+                None => false,
+            },
+            Reason::Idle => handler.queue().is_empty(),
+        }
+    }
+}
+
+/// Every guild's pending disconnects, each new one for the same reason
+/// bumps its generation so the older timers know they're stale.
+#[derive(Debug, Default)]
+pub struct Timers {
+    text_channels: HashMap<GuildId, ChannelId>,
+    generations: HashMap<(GuildId, Reason), u64>,
+}
+
+impl TypeMapKey for Timers {
+    type Value = Arc<Mutex<Timers>>;
+}
+
+async fn timers(ctx: &Context) -> Arc<Mutex<Timers>> {
+    ctx.data
+        .read()
+        .await
+        .get::<Timers>()
+        .cloned()
+        .expect("Timers placed in at initialisation.")
+}
+
+/// Remembers where to announce leaving for a guild that just connected.
+pub async fn bind(ctx: &Context, guild_id: GuildId, chan_id: ChannelId) {
+    timers(ctx)
+        .await
+        .lock()
+        .await
+        .text_channels
+        .insert(guild_id, chan_id);
+}
+
+/// Forgets a guild that left on its own, invalidating its pending timers.
+pub async fn unbind(ctx: &Context, guild_id: GuildId) {
+    let timers = timers(ctx).await;
+    let mut timers = timers.lock().await;
+
+    timers.text_channels.remove(&guild_id);
+    for reason in [Reason::EmptyChannel, Reason::Idle] {
+        *timers.generations.entry((guild_id, reason)).or_default() += 1;
+    }
+}
+
+/// Leaves `guild_id` after the timeout for `reason`, unless it's cancelled,
+/// rescheduled, or no longer true by then.
+pub async fn schedule(ctx: &Context, guild_id: GuildId, reason: Reason) {
+    let timeout = reason.timeout();
+    if timeout.is_zero() || guild_settings(ctx, guild_id).await.always_on {
+        return;
+    }
+
+    let generation = {
+        let timers = timers(ctx).await;
+        let mut timers = timers.lock().await;
+        let generation = timers.generations.entry((guild_id, reason)).or_default();
+        *generation += 1;
+        *generation
+    };
+
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+
+        let chan_id = {
+            let timers = timers(&ctx).await;
+            let timers = timers.lock().await;
+            if timers.generations.get(&(guild_id, reason)) != Some(&generation) {
+                return;
+            }
+            timers.text_channels.get(&guild_id).copied()
+        };
+
+        // a 24/7 toggle or a new listener since doesn't bump the generation
+        if guild_settings(&ctx, guild_id).await.always_on || !reason.holds(&ctx, guild_id).await {
+            return;
+        }
+
+        leave(&ctx, guild_id, chan_id, reason).await;
+    });
+}
+
+/// Stops a pending disconnect for `reason`.
+pub async fn cancel(ctx: &Context, guild_id: GuildId, reason: Reason) {
+    let timers = timers(ctx).await;
+    *timers
+        .lock()
+        .await
+        .generations
+        .entry((guild_id, reason))
+        .or_default() += 1;
+}
+
+/// Schedules or cancels leaving an empty channel, whichever fits now.
+pub async fn check_listeners(ctx: &Context, guild_id: GuildId) {
+    if Reason::EmptyChannel.holds(ctx, guild_id).await {
+        schedule(ctx, guild_id, Reason::EmptyChannel).await;
+    } else {
+        cancel(ctx, guild_id, Reason::EmptyChannel).await;
+    }
+}
+
+/// Everyone, not counting bots, in `channel`.
+pub async fn listeners(ctx: &Context, guild_id: GuildId, channel: ChannelId) -> HashSet<UserId> {
+    let me = ctx.cache.current_user_id().await;
+
+    ctx.cache
+        .guild_field(guild_id, |guild| {
+            guild
+                .voice_states
+                .values()
+                .filter(|state| state.channel_id == Some(channel) && state.user_id != me)
+                .filter(|state| !state.member.as_ref().is_some_and(|m| m.user.bot))
+                .map(|state| state.user_id)
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+}
+
+async fn leave(ctx: &Context, guild_id: GuildId, chan_id: Option<ChannelId>, reason: Reason) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let tracks = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current_queue(),
+        None => Vec::new(),
+    };
+
+    if let Err(why) = manager.remove(guild_id).await {
+        tracing::error!("Couldn't leave {}: {:?}", guild_id, why);
+        return;
+    }
+    // unlike `~leave` the saved queue stays for `~restore`, this wasn't
+    // anyone's choice
+    attachments::set_aside(ctx, guild_id, &tracks).await;
+    if let Err(why) = queue_store(ctx).await.set_aside(guild_id).await {
+        tracing::error!("Couldn't set the saved queue aside: {:?}", why);
+    }
+    set_queue_looping(ctx, guild_id, false).await;
+    unbind(ctx, guild_id).await;
+
+    if let Some(chan_id) = chan_id {
+        let prefix = guild_settings(ctx, guild_id).await.prefix().to_string();
+        check_msg(chan_id.say(&ctx.http, reason.announcement(&prefix)).await);
+    }
+}
+
+/// Starts the idle timer whenever the queue runs dry.
+pub struct QueueEndWatcher {
+    pub guild_id: GuildId,
+    pub context: Context,
+}
+
+#[async_trait]
+impl VoiceEventHandler for QueueEndWatcher {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        // a looping queue puts the song back before this runs, and `schedule`
+        // checks again before leaving anyway
+        if Reason::Idle.holds(&self.context, self.guild_id).await {
+            schedule(&self.context, self.guild_id, Reason::Idle).await;
+        }
+
+        None
+    }
+}
@@ -0,0 +1,230 @@
+//! Lazily started sources, so a long queue is just a list of metadata until
+//! its songs get close to playing.
+use std::{
+    ffi::OsStr,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use serenity::async_trait;
+use songbird::{
+    input::{
+        children_to_reader,
+        error::{Error, Result},
+        reader::MediaSource,
+        restartable::{Restart, Restartable},
+        Codec, Container, Input, Metadata, Reader,
+    },
+    Call,
+};
+use tokio::sync::oneshot;
+
+use crate::config;
+
+/// Resolves to whether the first start of a source managed to buffer
+/// [`config::Config::prebuffer_audio_secs`] of audio.
+pub type Buffered = oneshot::Receiver<bool>;
+
+/// Creates a source for `uri` without starting youtube-dl or ffmpeg.
+///
+/// With `metadata` already known nothing gets spawned at all, otherwise
+/// youtube-dl is asked for the metadata only.
+pub async fn ytdl(uri: String, metadata: Option<Metadata>) -> Result<(Restartable, Buffered)> {
+    let (buffered, rx) = oneshot::channel();
+    let source = Restartable::new(
+        LazyYtdl {
+            uri,
+            metadata,
+            buffered: Some(buffered),
+        },
+        true,
+    )
+    .await?;
+
+    Ok((source, rx))
+}
+
+/// Creates a source for a file or URL ffmpeg can read by itself, only
+/// ffprobe runs until it's played.
+///
+/// There's no pipe to wait on, so it counts as buffered straight away.
+pub async fn ffmpeg<P>(path: P) -> Result<(Restartable, Buffered)>
+where
+    P: AsRef<OsStr> + Send + Clone + Sync + 'static,
+{
+    let source = Restartable::ffmpeg(path, true).await?;
+
+    let (buffered, rx) = oneshot::channel();
+    let _ = buffered.send(true);
+
+    Ok((source, rx))
+}
+
+/// Starts the first few tracks of the queue in the background, so they're
+/// ready by the time they come up. Starting a track twice does nothing.
+pub fn preload(handler: &Call) {
+    // the one playing, and the ones after it
+    for track in handler
+        .queue()
+        .current_queue()
+        .iter()
+        .take(config::get().preload_ahead + 1)
+    {
+        let _ = track.make_playable();
+    }
+}
+
+struct LazyYtdl {
+    uri: String,
+    metadata: Option<Metadata>,
+    /// Told how the first start went, later ones are seeks nobody waits on.
+    buffered: Option<oneshot::Sender<bool>>,
+}
+
+#[async_trait]
+impl Restart for LazyYtdl {
+    async fn call_restart(&mut self, time: Option<Duration>) -> Result<Input> {
+        let ytdl_args = [
+            "-f",
+            "webm[abr>0]/bestaudio/best",
+            "-R",
+            "infinite",
+            "--no-playlist",
+            "--ignore-config",
+            "--no-warnings",
+            &self.uri,
+            "-o",
+            "-",
+        ];
+
+        let ffmpeg_args = [
+            "-f",
+            "s16le",
+            "-ac",
+            "2",
+            "-ar",
+            "48000",
+            "-acodec",
+            "pcm_f32le",
+            "-",
+        ];
+
+        let mut youtube_dl = Command::new("youtube-dl")
+            .args(ytdl_args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let taken_stdout = youtube_dl.stdout.take().expect("Stdout was piped.");
+
+        let mut ffmpeg = Command::new("ffmpeg");
+        if let Some(time) = time {
+            ffmpeg.args(["-ss", &format!("{:.3}", time.as_secs_f64())]);
+        }
+        let ffmpeg = ffmpeg
+            .arg("-i")
+            .arg("-")
+            .args(ffmpeg_args)
+            .stdin(taken_stdout)
+            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let reader = children_to_reader::<f32>(vec![youtube_dl, ffmpeg]);
+        let result = prebuffer(reader).await;
+
+        if let Some(buffered) = self.buffered.take() {
+            let _ = buffered.send(matches!(result, Ok(ref r) if r.ready));
+        }
+
+        Ok(Input::new(
+            true,
+            Reader::Extension(Box::new(result?)),
+            Codec::FloatPcm,
+            Container::Raw,
+            self.metadata.clone(),
+        ))
+    }
+
+    async fn lazy_init(&mut self) -> Result<(Option<Metadata>, Codec, Container)> {
+        let metadata = match &self.metadata {
+            Some(metadata) => metadata.clone(),
+            None => {
+                // songbird's own lazy source only runs `youtube-dl -j`
+                let input: Input = Restartable::ytdl(self.uri.clone(), true).await?.into();
+                let metadata = *input.metadata;
+
+                // searches have to play the video they found now, not whatever they find later
+                if let Some(url) = &metadata.source_url {
+                    self.uri = url.clone();
+                }
+                self.metadata = Some(metadata.clone());
+
+                metadata
+            }
+        };
+
+        Ok((Some(metadata), Codec::FloatPcm, Container::Raw))
+    }
+}
+
+/// 48kHz stereo f32.
+const BYTES_PER_SEC: usize = 48_000 * 2 * 4;
+
+/// Reads the first few seconds of `reader` into memory, so the song doesn't
+/// stutter while youtube-dl is still finding its feet.
+async fn prebuffer(mut reader: Reader) -> Result<Prebuffered> {
+    let want = config::get().prebuffer_audio_secs as usize * BYTES_PER_SEC;
+
+    // the pipe blocks, keep it off the runtime
+    tokio::task::spawn_blocking(move || -> Result<Prebuffered> {
+        let mut head = Vec::with_capacity(want);
+        (&mut reader).take(want as u64).read_to_end(&mut head)?;
+
+        Ok(Prebuffered {
+            // an empty pipe means youtube-dl or ffmpeg died on us
+            ready: !head.is_empty() || want == 0,
+            head: Cursor::new(head),
+            rest: reader,
+        })
+    })
+    .await
+    .map_err(|_| Error::Stdout)?
+}
+
+/// A pipe with its first few seconds already read.
+struct Prebuffered {
+    ready: bool,
+    head: Cursor<Vec<u8>>,
+    rest: Reader,
+}
+
+impl Read for Prebuffered {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.head.read(buf)? {
+            0 => self.rest.read(buf),
+            n => Ok(n),
+        }
+    }
+}
+
+impl Seek for Prebuffered {
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Pipes can't seek, restart them instead.",
+        ))
+    }
+}
+
+impl MediaSource for Prebuffered {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn len(&self) -> Option<u64> {
+        None
+    }
+}
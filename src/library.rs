@@ -0,0 +1,195 @@
+//! The local music library, audio files under the configured `music_dir`.
+//!
+//! Songs are referred to by their path relative to the library root, and
+//! nothing outside of it can be played, however the path is spelled.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serenity::async_trait;
+use songbird::input::{Input, Metadata};
+
+use crate::{
+    config,
+    error::{Result, SourceError},
+    lazy,
+    sources::{Resolved, Song, SourceResolver},
+};
+
+/// Extensions worth offering, anything else under the root is ignored by searches.
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "flac", "ogg", "opus", "wav", "m4a", "aac", "wma", "webm", "mka", "mp4",
+];
+
+/// The configured library root, `None` if there's no library.
+pub fn root() -> Option<&'static Path> {
+    config::get().music_dir.as_deref()
+}
+
+/// Turns `relative` into a full path, `None` unless it's an existing audio
+/// file inside the library. Symlinks and `..` are resolved before checking.
+pub fn resolve(relative: &Path) -> Option<PathBuf> {
+    resolve_in(root()?, relative)
+}
+
+fn resolve_in(root: &Path, relative: &Path) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    let path = root.join(relative).canonicalize().ok()?;
+
+    (path.starts_with(&root) && path.is_file() && is_audio(relative)).then_some(path)
+}
+
+/// Finds `query` in the library: the file at that exact relative path if
+/// there is one, otherwise every audio file whose path contains all of its
+/// words, ignoring case. Paths come back relative to the root, sorted.
+pub fn find(query: &str) -> io::Result<Vec<PathBuf>> {
+    let root = match root() {
+        Some(root) => root.canonicalize()?,
+        None => return Ok(Vec::new()),
+    };
+
+    let exact = Path::new(query);
+    if exact.is_relative() && resolve(exact).is_some() {
+        return Ok(vec![exact.to_path_buf()]);
+    }
+
+    let words = query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+
+    let mut files = Vec::new();
+    walk(&root, &root, &mut files)?;
+
+    let mut found = files
+        .into_iter()
+        .filter(|file| {
+            let name = file.to_string_lossy().to_lowercase();
+            words.iter().all(|word| name.contains(word.as_str()))
+        })
+        .collect::<Vec<_>>();
+    found.sort();
+
+    Ok(found)
+}
+
+/// Collects the audio files under `dir`, relative to `root`. Symlinked
+/// directories are skipped so a loop can't keep us here forever.
+fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            walk(root, &path, files)?;
+        } else if is_audio(&path) {
+            if let Ok(relative) = path.strip_prefix(root) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `path` has one of the audio file extensions.
+pub fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Files in the library, by their path relative to the root.
+pub struct LocalResolver;
+
+impl LocalResolver {
+    pub const NAME: &'static str = "local";
+}
+
+#[async_trait]
+impl SourceResolver for LocalResolver {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    /// `~local` picks these itself.
+    fn handles(&self, _query: &str) -> bool {
+        false
+    }
+
+    async fn resolve(&self, query: &str, _known: Option<Metadata>) -> Result<Resolved> {
+        let relative = Path::new(query);
+
+        // checked again every time, the library might have changed since
+        let path = match resolve(relative) {
+            Some(path) => path,
+            None => {
+                return Err(SourceError::NotFound(format!(
+                    "`{}` isn't in the music library",
+                    query
+                ))
+                .into())
+            }
+        };
+
+        let (source, buffered) = lazy::ffmpeg(path).await?;
+
+        let input: Input = source.into();
+        let mut metadata = *input.metadata.clone();
+
+        // files without tags are still better known by their name than "<no title>"
+        if metadata.title.is_none() {
+            metadata.title = relative
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+        }
+
+        Ok(Resolved {
+            input,
+            metadata,
+            buffered,
+            song: Song::new(Self::NAME, query),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_stays_inside_the_library() {
+        let dir = std::env::temp_dir().join(format!("aoede-library-{}", std::process::id()));
+        let root = dir.join("music");
+        fs::create_dir_all(root.join("album")).unwrap();
+        fs::write(root.join("album/song.mp3"), b"").unwrap();
+        fs::write(root.join("notes.txt"), b"").unwrap();
+        fs::write(dir.join("secret.mp3"), b"").unwrap();
+
+        let resolved = |relative: &str| resolve_in(&root, Path::new(relative));
+        assert!(resolved("album/song.mp3").is_some());
+        assert!(resolved("album/../album/song.mp3").is_some());
+        assert!(resolved("album/missing.mp3").is_none());
+        assert!(resolved("notes.txt").is_none());
+        assert!(resolved("../secret.mp3").is_none());
+        assert!(resolved(dir.join("secret.mp3").to_str().unwrap()).is_none());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("secret.mp3"), root.join("link.mp3")).unwrap();
+            assert!(resolved("link.mp3").is_none());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn audio_extensions_ignore_case() {
+        assert!(is_audio(Path::new("a/b.MP3")));
+        assert!(is_audio(Path::new("b.flac")));
+        assert!(!is_audio(Path::new("b.txt")));
+        assert!(!is_audio(Path::new("mp3")));
+    }
+}
@@ -9,176 +9,589 @@
 //! git = "https://github.com/serenity-rs/serenity.git"
 //! features = ["cache", "framework", "standard_framework", "voice"]
 //! ```
+mod attachments;
+mod config;
+mod error;
+mod idle;
+mod lazy;
+mod library;
+mod perms;
+mod persist;
+mod settings;
+mod slash;
+mod sources;
+
 use std::{
-    env,
+    collections::{HashMap, HashSet},
+    fmt, io,
+    path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use serenity::{
     async_trait,
+    builder::CreateEmbed,
     client::{Client, Context, EventHandler},
     framework::{
         standard::{
-            macros::{command, group},
-            Args, CommandResult,
+            macros::{check, command, group, hook},
+            Args, CommandOptions, CommandResult, DispatchError, Reason,
         },
         StandardFramework,
     },
     http::Http,
     model::{
-        channel::Message,
-        gateway::{Activity, Ready},
-        id::GuildId,
+        channel::{Attachment, Message},
+        gateway::Ready,
+        id::{GuildId, RoleId, UserId},
+        interactions::Interaction,
         misc::Mentionable,
         prelude::ChannelId,
+        voice::VoiceState,
     },
-    prelude::{RwLock, TypeMapKey},
+    prelude::{Mutex, RwLock, TypeMapKey},
     Result as SerenityResult,
 };
 
+use rand::seq::SliceRandom;
 use songbird::{
+    error::JoinError,
     events::EventStore,
-    input::{self, restartable::Restartable},
-    input::{Input, Metadata},
-    tracks::{self, LoopState, TrackError},
+    input::{self, Metadata},
+    tracks::{self, LoopState, PlayMode, TrackHandle},
     Call, Event, EventContext, EventHandler as VoiceEventHandler, SerenityInit, TrackEvent,
 };
-use youtube_dl::{YoutubeDl, YoutubeDlOutput};
+use youtube_dl::{SearchOptions, SingleVideo, YoutubeDl, YoutubeDlOutput};
+
+use attachments::AttachmentResolver;
+use config::Config;
+use error::{AoedeError, Result, SourceError, VoiceError};
+use library::LocalResolver;
+use persist::{QueueSnapshot, QueueStore, Queues};
+use settings::{GuildSettings, Settings, SettingsStore};
+use sources::{Resolved, Resolvers, Song, Ytdl};
+
+fn icon() -> &'static str {
+    &config::get().icon
+}
+
+fn embed_colour() -> (u8, u8, u8) {
+    config::get().embed_colour
+}
 
-static ICON: &'static str =
-    "https://cdn.discordapp.com/avatars/887241846869360641/70525dd8fab9290f78cc7ad2e26728a6.webp";
-static EMBED_COLOUR: (u8, u8, u8) = (253, 195, 213);
-struct Handler;
+struct Handler {
+    /// `ready` fires again on every reconnect, saved queues are only offered once.
+    restores_offered: AtomicBool,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
-        ctx.set_activity(Activity::watching("your mother")).await;
+        tracing::info!("{} is connected!", ready.user.name);
+        ctx.set_activity(config::get().activity.to_activity()).await;
+
+        if !self.restores_offered.swap(true, Ordering::Relaxed) {
+            offer_restores(&ctx).await;
+        }
+
+        if let Err(why) = slash::register(&ctx).await {
+            tracing::error!("Couldn't register slash commands: {:?}", why);
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::ApplicationCommand(command) = interaction {
+            slash::run(&ctx, &command).await;
+        }
+    }
+
+    async fn voice_state_update(
+        &self,
+        ctx: Context,
+        guild_id: Option<GuildId>,
+        _old: Option<VoiceState>,
+        _new: VoiceState,
+    ) {
+        // someone came or went, maybe from our channel
+        if let Some(guild_id) = guild_id {
+            idle::check_listeners(&ctx, guild_id).await;
+        }
     }
 }
 
 #[group]
 #[commands(
-    join, leave, play_fade, play, play_playlist,/*queue,*/ skip, stop, ping, nowplaying, songloop
+    join,
+    leave,
+    play_fade,
+    play,
+    search,
+    local,
+    play_playlist,
+    queue,
+    skip,
+    voteskip,
+    stop,
+    remove,
+    move_cmd,
+    swap,
+    skipto,
+    shuffle,
+    ping,
+    nowplaying,
+    songloop,
+    loop_cmd,
+    abloop,
+    volume,
+    pause,
+    resume,
+    toggle,
+    seek,
+    forward,
+    rewind,
+    restore,
+    prefix,
+    always_on,
+    dj,
+    playlistmax
 )]
+#[checks(Music)]
 struct General;
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    // Configure the client with your Discord bot token in the environment.
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
+    let (config_path, explicit) = Config::path_from_args();
+    let config = match Config::load(&config_path, explicit) {
+        Ok(config) => config,
+        Err(why) => {
+            eprintln!("Error in config: {}", why);
+            std::process::exit(1);
+        }
+    };
+
+    let token = match config.token() {
+        Ok(token) => token,
+        Err(why) => {
+            eprintln!("Error in config: {}", why);
+            std::process::exit(1);
+        }
+    };
+
+    let settings = match SettingsStore::load(&config.settings_path) {
+        Ok(settings) => settings,
+        Err(why) => {
+            eprintln!(
+                "Couldn't load guild settings from {}: {}",
+                config.settings_path.display(),
+                why
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let queues = match QueueStore::new(&config.queue_dir) {
+        Ok(queues) => queues,
+        Err(why) => {
+            eprintln!(
+                "Couldn't open the queue directory {}: {}",
+                config.queue_dir.display(),
+                why
+            );
+            std::process::exit(1);
+        }
+    };
+
+    config::init(config);
+
+    // uploads only outlive the bot for queues that can still be restored
+    let mut restorable = Vec::new();
+    for guild_id in queues.pending_guilds().await.unwrap_or_default() {
+        if let Ok(Some(snapshot)) = queues.load_pending(guild_id).await {
+            restorable.extend(
+                snapshot
+                    .tracks
+                    .into_iter()
+                    .filter(|track| track.source.resolver == AttachmentResolver::NAME)
+                    .map(|track| PathBuf::from(track.source.query)),
+            );
+        }
+    }
+    attachments::sweep(&restorable);
+
+    // slash commands are registered against the application, which for bots
+    // can be looked up with the token itself
+    let application_id = match Http::new_with_token(&token)
+        .get_current_application_info()
+        .await
+    {
+        Ok(info) => info.id,
+        Err(why) => {
+            eprintln!(
+                "Couldn't fetch the application info, is the token right? {}",
+                why
+            );
+            std::process::exit(1);
+        }
+    };
 
+    // no static prefix, every guild gets its own through the dynamic one
     let framework = StandardFramework::new()
-        .configure(|c| c.prefix("~"))
+        .configure(|c| {
+            c.prefix("").dynamic_prefix(|ctx, msg| {
+                Box::pin(async move { Some(prefix_for(ctx, msg).await) })
+            })
+        })
+        .after(after)
+        .on_dispatch_error(dispatch_error)
         .group(&GENERAL_GROUP);
 
     let mut client = Client::builder(&token)
-        .event_handler(Handler)
+        .application_id(application_id.0)
+        .event_handler(Handler {
+            restores_offered: AtomicBool::new(false),
+        })
         .framework(framework)
         .register_songbird()
         .await
         .expect("Err creating client");
 
+    {
+        let mut data = client.data.write().await;
+        data.insert::<Settings>(Arc::new(RwLock::new(settings)));
+        data.insert::<Queues>(queues);
+        data.insert::<QueueLoops>(Arc::new(RwLock::new(HashSet::new())));
+        data.insert::<Resolvers>(Arc::new(Resolvers::default()));
+        data.insert::<VoteSkips>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<idle::Timers>(Arc::new(Mutex::new(idle::Timers::default())));
+        data.insert::<attachments::SetAside>(Arc::new(Mutex::new(HashMap::new())));
+    }
+
     let _ = client
         .start()
         .await
-        .map_err(|why| println!("Client ended: {:?}", why));
+        .map_err(|why| tracing::error!("Client ended: {:?}", why));
+}
+
+// runs the `perms` rules of whichever command is being called
+#[check]
+#[name = "Music"]
+async fn music_check(
+    ctx: &Context,
+    msg: &Message,
+    _: &mut Args,
+    options: &CommandOptions,
+) -> Result<(), Reason> {
+    // `only_in(guilds)` turns these away on its own
+    if msg.guild_id.is_none() {
+        return Ok(());
+    }
+
+    perms::check(ctx, &Invocation::from(msg), options.names[0])
+        .await
+        .map_err(|why| Reason::User(why.user_message()))
+}
+
+/// Tells whoever ran a command that failed what went wrong.
+#[hook]
+async fn after(ctx: &Context, msg: &Message, command_name: &str, result: CommandResult) {
+    if let Err(why) = result {
+        AoedeError::from(why)
+            .report(command_name)
+            .send(ctx, msg.channel_id)
+            .await;
+    }
+}
+
+#[hook]
+async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
+    let why = match error {
+        DispatchError::CheckFailed(_, Reason::User(why)) => why,
+        DispatchError::LackingPermissions(permissions) => format!(
+            "You need the {} permission for that",
+            permissions.get_permission_names().join(", ")
+        ),
+        DispatchError::OnlyForGuilds => "That only works in a server".into(),
+        _ => return,
+    };
+
+    // the hook isn't told which command it was, it's whatever follows the prefix
+    let prefix = prefix_for(ctx, msg).await;
+    let command_name = msg
+        .content
+        .strip_prefix(prefix.as_str())
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_default();
+
+    AoedeError::Permission(why)
+        .report(command_name)
+        .send(ctx, msg.channel_id)
+        .await;
+}
+
+/// The prefix commands have where `msg` was sent.
+async fn prefix_for(ctx: &Context, msg: &Message) -> String {
+    match msg.guild_id {
+        Some(guild_id) => guild_settings(ctx, guild_id).await.prefix().to_string(),
+        None => config::get().prefix.clone(),
+    }
+}
+
+/// Where a command was called from, this is all the playback code gets to
+/// see so `~` commands and slash commands end up doing the exact same thing.
+#[derive(Clone, Copy, Debug)]
+struct Invocation {
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    author: UserId,
+}
+
+impl From<&Message> for Invocation {
+    fn from(msg: &Message) -> Self {
+        Self {
+            // every music command is `only_in(guilds)`
+            guild_id: msg
+                .guild_id
+                .expect("Guild only command called outside a guild"),
+            channel_id: msg.channel_id,
+            author: msg.author.id,
+        }
+    }
+}
+
+/// The final answer of a command, said in the channel for `~` commands and
+/// used as the interaction response for slash commands.
+enum Reply {
+    Text(String),
+    Embed {
+        content: Option<String>,
+        embed: CreateEmbed,
+    },
+}
+
+impl Reply {
+    fn text(text: impl Into<String>) -> Self {
+        Reply::Text(text.into())
+    }
+
+    fn embed<F>(content: Option<String>, f: F) -> Self
+    where
+        F: FnOnce(&mut CreateEmbed) -> &mut CreateEmbed,
+    {
+        let mut embed = CreateEmbed::default();
+        f(&mut embed);
+
+        Reply::Embed { content, embed }
+    }
+
+    async fn send(self, ctx: &Context, chan_id: ChannelId) {
+        check_msg(
+            chan_id
+                .send_message(&ctx.http, |m| match self {
+                    Reply::Text(text) => m.content(text),
+                    Reply::Embed { content, embed } => {
+                        if let Some(content) = content {
+                            m.content(content);
+                        }
+                        m.set_embed(embed)
+                    }
+                })
+                .await,
+        );
+    }
 }
 
 #[command]
 #[only_in(guilds)]
 async fn join(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
+    join_voice(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn join_voice(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild = inv
+        .guild_id
+        .to_guild_cached(&ctx.cache)
+        .await
+        .ok_or(AoedeError::GuildUnavailable)?;
     let guild_id = guild.id;
 
-    let channel_id = guild
+    let connect_to = guild
         .voice_states
-        .get(&msg.author.id)
-        .and_then(|voice_state| voice_state.channel_id);
+        .get(&inv.author)
+        .and_then(|voice_state| voice_state.channel_id)
+        .ok_or(VoiceError::NoChannel)?;
 
-    let connect_to = match channel_id {
-        Some(channel) => channel,
-        None => {
-            check_msg(msg.reply(ctx, "Not in a voice channel").await);
+    connect(ctx, guild_id, connect_to, inv.channel_id).await?;
 
-            return Ok(());
-        }
-    };
+    Ok(Reply::text(format!("Joined {}", connect_to.mention())))
+}
 
+/// Joins the author's voice channel unless already in one, so the play
+/// commands work without `~join`.
+async fn join_if_needed(ctx: &Context, inv: &Invocation) -> Result<()> {
     let manager = songbird::get(ctx)
         .await
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
 
-    let (handle_lock, success) = manager.join(guild_id, connect_to).await;
+    if manager.get(inv.guild_id).is_some() {
+        return Ok(());
+    }
 
-    if let Ok(_channel) = success {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, &format!("Joined {}", connect_to.mention()))
-                .await,
-        );
+    let channel_id = ctx
+        .cache
+        .guild_field(inv.guild_id, |guild| {
+            guild
+                .voice_states
+                .get(&inv.author)
+                .and_then(|voice_state| voice_state.channel_id)
+        })
+        .await
+        .flatten();
 
-        let chan_id = msg.channel_id;
+    let connect_to = channel_id.ok_or(VoiceError::NoChannel)?;
+    connect(ctx, inv.guild_id, connect_to, inv.channel_id).await?;
 
-        let send_http = ctx.http.clone();
+    check_msg(
+        inv.channel_id
+            .say(&ctx.http, format!("Joined {}", connect_to.mention()))
+            .await,
+    );
 
-        let mut handle = handle_lock.lock().await;
-        match handle.deafen(true).await {
-            Ok(_) => {}
-            Err(_) => check_msg(msg.channel_id.say(&ctx.http, "There was an error while trying to deafen, vivian didn't care enough to handle this, if this keeps happening and you can't fix it contact her").await),
-        }
+    Ok(())
+}
 
-        handle.add_global_event(
-            Event::Track(TrackEvent::End),
-            TrackEndNotifier {
-                chan_id,
-                http: send_http,
-            },
-        );
+/// Joins a voice channel and sets up the events every call needs, `chan_id`
+/// is the text channel those events talk in.
+async fn connect(
+    ctx: &Context,
+    guild_id: GuildId,
+    connect_to: ChannelId,
+    chan_id: ChannelId,
+) -> Result<Arc<Mutex<Call>>, JoinError> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
 
-        //let send_http = ctx.http.clone();
+    let (handle_lock, success) = manager.join(guild_id, connect_to).await;
+    success?;
 
-        /*
-        handle.add_global_event(
-            Event::Periodic(Duration::from_secs(60), None),
-            ChannelDurationNotifier {
-                chan_id,
-                count: Default::default(),
-                http: send_http,
-            },
-        );
-        */
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Error joining the channel")
-                .await,
-        );
+    let mut handle = handle_lock.lock().await;
+    match handle.deafen(true).await {
+        Ok(_) => {}
+        Err(_) => check_msg(chan_id.say(&ctx.http, "There was an error while trying to deafen, vivian didn't care enough to handle this, if this keeps happening and you can't fix it contact her").await),
     }
 
-    Ok(())
+    handle.add_global_event(
+        Event::Track(TrackEvent::End),
+        TrackEndNotifier {
+            guild_id,
+            chan_id,
+            context: ctx.clone(),
+        },
+    );
+
+    handle.add_global_event(
+        Event::Track(TrackEvent::End),
+        QueueLooper {
+            guild_id,
+            chan_id,
+            context: ctx.clone(),
+        },
+    );
+
+    handle.add_global_event(
+        Event::Track(TrackEvent::End),
+        QueuePreloader {
+            guild_id,
+            context: ctx.clone(),
+        },
+    );
+
+    handle.add_global_event(
+        Event::Track(TrackEvent::End),
+        QueueSaver {
+            guild_id,
+            chan_id,
+            context: ctx.clone(),
+        },
+    );
+
+    // positions move on their own, keep the snapshot from going too stale
+    handle.add_global_event(
+        Event::Periodic(Duration::from_secs(10), None),
+        QueueSaver {
+            guild_id,
+            chan_id,
+            context: ctx.clone(),
+        },
+    );
+
+    //let send_http = ctx.http.clone();
+
+    /*
+    handle.add_global_event(
+        Event::Periodic(Duration::from_secs(60), None),
+        ChannelDurationNotifier {
+            chan_id,
+            count: Default::default(),
+            http: send_http,
+        },
+    );
+    */
+
+    handle.add_global_event(
+        Event::Track(TrackEvent::End),
+        idle::QueueEndWatcher {
+            guild_id,
+            context: ctx.clone(),
+        },
+    );
+
+    drop(handle);
+
+    // nothing's playing yet, and maybe nobody's listening either
+    idle::bind(ctx, guild_id, chan_id).await;
+    idle::schedule(ctx, guild_id, idle::Reason::Idle).await;
+    idle::check_listeners(ctx, guild_id).await;
+
+    Ok(handle_lock)
 }
 
 struct TrackEndNotifier {
+    guild_id: GuildId,
     chan_id: ChannelId,
-    http: Arc<Http>,
+    context: Context,
 }
 
 #[async_trait]
 impl VoiceEventHandler for TrackEndNotifier {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track_list) = ctx {
+            // votes were for the song that just ended
+            vote_skips(&self.context)
+                .await
+                .lock()
+                .await
+                .remove(&self.guild_id);
+
             check_msg(
                 self.chan_id
-                    .say(&self.http, &format!("Tracks ended: {}.", track_list.len()))
+                    .say(
+                        &self.context.http,
+                        &format!("Tracks ended: {}.", track_list.len()),
+                    )
                     .await,
             );
         }
@@ -218,8 +631,16 @@ impl VoiceEventHandler for ChannelDurationNotifier {
 #[command]
 #[only_in(guilds)]
 async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+    leave_voice(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn leave_voice(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild_id = inv.guild_id;
 
     let manager = songbird::get(ctx)
         .await
@@ -228,35 +649,46 @@ async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
     let has_handler = manager.get(guild_id).is_some();
 
     if has_handler {
-        if let Err(e) = manager.remove(guild_id).await {
-            check_msg(
-                msg.channel_id
-                    .say(&ctx.http, format!("Failed: {:?}", e))
-                    .await,
-            );
+        manager.remove(guild_id).await?;
+
+        set_queue_looping(ctx, guild_id, false).await;
+        idle::unbind(ctx, guild_id).await;
+
+        // leaving on purpose means the queue is gone for good
+        if let Err(why) = queue_store(ctx).await.remove(guild_id).await {
+            tracing::error!("Couldn't remove saved queue: {:?}", why);
         }
 
-        check_msg(msg.channel_id.say(&ctx.http, "Left voice channel").await);
+        Ok(Reply::text("Left voice channel"))
     } else {
-        check_msg(msg.reply(ctx, "Not in a voice channel").await);
+        Ok(Reply::text("Not in a voice channel"))
     }
-
-    Ok(())
 }
 
 #[command]
 async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
-    check_msg(msg.channel_id.say(&ctx.http, "Pong!").await);
+    pong().send(ctx, msg.channel_id).await;
 
     Ok(())
 }
 
+fn pong() -> Reply {
+    Reply::text("Pong!")
+}
+
 #[command]
 #[only_in(guilds)]
-#[aliases("loop")]
 async fn songloop(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+    toggle_song_loop(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn toggle_song_loop(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild_id = inv.guild_id;
 
     let manager = songbird::get(ctx)
         .await
@@ -271,467 +703,440 @@ async fn songloop(ctx: &Context, msg: &Message) -> CommandResult {
         let current_song = match queue.current() {
             Some(song) => song,
             None => {
-                check_msg(
-                    msg.channel_id
-                        .say(
-                            &ctx.http,
-                            "No song is playing, please, i beg you, play a song, ᵖˡᵉᵃˢᵉ",
-                        )
-                        .await,
-                );
-                return Ok(());
+                return Ok(Reply::text(
+                    "No song is playing, please, i beg you, play a song, ᵖˡᵉᵃˢᵉ",
+                ))
             }
         };
 
-        match current_song.get_info().await {
-            Ok(info) => {
-                if info.loops != LoopState::Infinite {
-                    enable_loop = true;
-                }
-            }
-            Err(e) => match e {
-                TrackError::Finished => check_msg(
-                    msg.channel_id
-                        .say(
-                            &ctx.http,
-                            "The song is already finished, i beg you, ᵖˡᵉᵃˢᵉ ᵠᵘᵉᵘᵉ ᵃⁿᵒᵗʰᵉʳ ᵒⁿᵉ",
-                        )
-                        .await,
-                ),
-                _ => check_msg(
-                    msg.channel_id
-                        .say(
-                            &ctx.http,
-                            "ok the dev actually done goofed, contact here and make fun of her (1)",
-                        )
-                        .await,
-                ),
-            },
+        if current_song.get_info().await?.loops != LoopState::Infinite {
+            enable_loop = true;
+        }
+
+        let toggled = if enable_loop {
+            set_queue_looping(ctx, guild_id, false).await;
+            current_song.enable_loop()
+        } else {
+            current_song.disable_loop()
         };
 
-        if enable_loop {
-            match current_song.enable_loop() {
-                Ok(_) => check_msg(msg.channel_id.say(&ctx.http, "Enabled infinite loop for the current song!").await),
-                Err(e) => match e {
-                    TrackError::Finished => check_msg(
-                        msg.channel_id
-                            .say(
-                                &ctx.http,
-                                "The song is already finished, i beg you, ᵖˡᵉᵃˢᵉ ᵠᵘᵉᵘᵉ ᵃⁿᵒᵗʰᵉʳ ᵒⁿᵉ",
-                            )
-                            .await,
-                    ),
-                    _ => check_msg(
-                        msg.channel_id
-                            .say(
-                                &ctx.http,
-                                "ok the dev actually done goofed, contact here and make fun of her (2)",
-                            )
-                            .await,
-                    ),
-                },
-            }
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        toggled?;
+
+        Ok(Reply::text(if enable_loop {
+            "Enabled infinite loop for the current song!"
         } else {
-            match current_song.disable_loop() {
-                Ok(_) => check_msg(msg.channel_id.say(&ctx.http, "Disabled infinite loop for the current song!").await),
-                Err(e) => match e {
-                    TrackError::Finished => check_msg(
-                        msg.channel_id
-                            .say(
-                                &ctx.http,
-                                "The song is already finished, i beg you, ᵖˡᵉᵃˢᵉ ᵠᵘᵉᵘᵉ ᵃⁿᵒᵗʰᵉʳ ᵒⁿᵉ",
-                            )
-                            .await,
-                    ),
-                    _ => check_msg(
-                        msg.channel_id
-                            .say(
-                                &ctx.http,
-                                "ok the dev actually done goofed, contact here and make fun of her (2)",
-                            )
-                            .await,
-                    ),
-                },
-            }
-        }
+            "Disabled infinite loop for the current song!"
+        }))
     } else {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
-                .await,
-        );
+        Err(VoiceError::NotConnected.into())
     }
-
-    Ok(())
 }
 
-#[command]
+#[command("loop")]
 #[only_in(guilds)]
-#[aliases("np")]
-async fn nowplaying(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+async fn loop_cmd(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let inv = Invocation::from(msg);
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
+    let reply = if args.is_empty() {
+        // what `~loop` always did
+        toggle_song_loop(ctx, &inv).await
+    } else {
+        match args
+            .single::<String>()
+            .ok()
+            .and_then(|m| LoopMode::parse(&m))
+        {
+            Some(mode) => set_loop_mode(ctx, &inv, mode).await,
+            None => Ok(Reply::text(
+                "Loop mode has to be `queue`, `track`, `off` or how many more times to play the song",
+            )),
+        }
+    };
 
-    let channel_id = guild
-        .voice_states
-        .get(&msg.author.id)
-        .and_then(|voice_state| voice_state.channel_id);
+    reply?.send(ctx, msg.channel_id).await;
 
-    let chan = match channel_id {
-        Some(channel) => channel,
-        None => {
-            check_msg(msg.reply(ctx, "You are not in a vc.").await);
+    Ok(())
+}
 
-            return Ok(());
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LoopMode {
+    Off,
+    /// The current track, over and over.
+    Track,
+    /// The current track, this many more times.
+    Times(usize),
+    /// Every finished track goes back to the end of the queue.
+    Queue,
+}
+
+impl LoopMode {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "off" | "none" => Some(LoopMode::Off),
+            "track" | "song" | "one" => Some(LoopMode::Track),
+            "queue" | "all" => Some(LoopMode::Queue),
+            _ => match text.parse() {
+                Ok(0) => Some(LoopMode::Off),
+                Ok(times) => Some(LoopMode::Times(times)),
+                Err(_) => None,
+            },
         }
-    };
+    }
+}
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        /*
-         * basic ~queue logic
-        let mut queue_str = String::new();
+impl fmt::Display for LoopMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopMode::Off => write!(f, "off"),
+            LoopMode::Track => write!(f, "track"),
+            LoopMode::Times(times) => write!(f, "track, {} more time(s)", times),
+            LoopMode::Queue => write!(f, "queue"),
+        }
+    }
+}
 
-        let queue = handler.queue().current_queue();
+/// Guilds looping their whole queue.
+struct QueueLoops;
 
-        for (n, i) in queue.iter().enumerate() {
-            let title = i.metadata().title.clone().unwrap_or("<no title>".into());
-            queue_str.push_str(&format!("[{n}] {title}\n"))
-        }
-        */
+impl TypeMapKey for QueueLoops {
+    type Value = Arc<RwLock<HashSet<GuildId>>>;
+}
 
-        let songtitle;
-        let thumblink;
-        let statusbar;
-        let duration;
+async fn queue_looping(ctx: &Context, guild_id: GuildId) -> bool {
+    let loops = ctx
+        .data
+        .read()
+        .await
+        .get::<QueueLoops>()
+        .cloned()
+        .expect("QueueLoops placed in at initialisation.");
 
-        let mut np_str = String::new();
+    let looping = loops.read().await.contains(&guild_id);
+    looping
+}
 
-        if let Some(current) = handler.queue().current() {
-            let md = current.metadata().clone();
-            songtitle = md.title.unwrap_or("<no title>".into());
-            thumblink = md.thumbnail;
-            let curpos = match current.get_info().await {
-                Ok(state) => state.position,
-                Err(e) => match e {
-                    TrackError::Finished => {
-                        check_msg(
-                            msg.channel_id
-                                .say(
-                                    &ctx.http,
-                                    "The song is finished and there are no more songs",
-                                )
-                                .await,
-                        );
-                        return Ok(());
-                    }
-                    _ => {
-                        check_msg(
-                            msg.channel_id
-                                .say(
-                                    &ctx.http,
-                                    "This error shouldn't occur? please contact the developer (err 1)",
-                                )
-                                .await,
-                        );
-                        return Ok(());
-                    }
-                },
-            };
+async fn set_queue_looping(ctx: &Context, guild_id: GuildId, looping: bool) {
+    let loops = ctx
+        .data
+        .read()
+        .await
+        .get::<QueueLoops>()
+        .cloned()
+        .expect("QueueLoops placed in at initialisation.");
 
-            np_str.push_str(":arrow_forward: ");
+    let mut loops = loops.write().await;
+    if looping {
+        loops.insert(guild_id);
+    } else {
+        loops.remove(&guild_id);
+    }
+}
 
-            // 13 dynamic symbols
-            // formula: (current seconds/duration seconds)*13
-            // current point is the round up of formula, fill the rest
+/// How the queue of `handler` is looping right now.
+async fn loop_mode(ctx: &Context, guild_id: GuildId, handler: &Call) -> LoopMode {
+    if queue_looping(ctx, guild_id).await {
+        return LoopMode::Queue;
+    }
 
-            // i think its safe to unwrap
-            let current_pointer =
-                ((curpos.as_secs_f64() / md.duration.unwrap().as_secs_f64()) * 13.).ceil();
+    match handler.queue().current() {
+        Some(current) => match current.get_info().await {
+            Ok(state) => match state.loops {
+                LoopState::Infinite => LoopMode::Track,
+                LoopState::Finite(0) => LoopMode::Off,
+                LoopState::Finite(times) => LoopMode::Times(times),
+            },
+            Err(_) => LoopMode::Off,
+        },
+        None => LoopMode::Off,
+    }
+}
 
-            let before = current_pointer - 1.;
-            let after = 13. - current_pointer;
+async fn set_loop_mode(ctx: &Context, inv: &Invocation, mode: LoopMode) -> Result<Reply> {
+    let guild_id = inv.guild_id;
 
-            for _ in 0..=before as u64 {
-                np_str.push('▬');
-            }
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
 
-            // the current pointer
-            np_str.push_str(":radio_button:");
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
 
-            for _ in 0..=after as u64 {
-                np_str.push('▬');
-            }
+        // the modes don't mix, a looping track would never let the queue move on
+        if let Some(current) = handler.queue().current() {
+            let _ = match mode {
+                LoopMode::Track => current.enable_loop(),
+                LoopMode::Times(times) => current.loop_for(times),
+                LoopMode::Off | LoopMode::Queue => current.disable_loop(),
+            };
+        } else if matches!(mode, LoopMode::Track | LoopMode::Times(_)) {
+            return Ok(Reply::text(
+                "No song is playing, please, i beg you, play a song, ᵖˡᵉᵃˢᵉ",
+            ));
+        }
 
-            np_str.push(' ');
+        set_queue_looping(ctx, guild_id, mode == LoopMode::Queue).await;
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
 
-            duration = format!(
-                "[{}/{}]",
-                hrtime::from_sec_padded(curpos.as_secs()),
-                hrtime::from_sec_padded(md.duration.unwrap().as_secs())
-            );
+        Ok(Reply::text(match mode {
+            LoopMode::Off => "Stopped looping".to_string(),
+            LoopMode::Track => "Looping the current song!".to_string(),
+            LoopMode::Times(times) => format!("Playing the current song {} more time(s)!", times),
+            LoopMode::Queue => "Looping the whole queue!".to_string(),
+        }))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
 
-            np_str.push_str(":loud_sound:");
+/// Puts a track back at the end of the queue, as if its requester queued it
+/// again.
+async fn requeue(
+    ctx: &Context,
+    guild_id: GuildId,
+    chan_id: ChannelId,
+    handler: &mut Call,
+    track: &TrackHandle,
+) {
+    let (source, requester) = {
+        let typemap = track.typemap().read().await;
+        match (typemap.get::<Source>(), typemap.get::<Requester>()) {
+            (Some(source), Some(requester)) => (source.clone(), *requester),
+            // not ours, no idea how to get it back
+            _ => return,
+        }
+    };
 
-            statusbar = np_str;
+    let inv = Invocation {
+        guild_id,
+        channel_id: chan_id,
+        author: requester,
+    };
 
-            check_msg(
-                msg.channel_id
-                    .send_message(&ctx.http, |m| {
-                        m.content(format!("Now playing (in {}): ", chan.mention()));
-                        m.embed(|e| {
-                            e.colour(EMBED_COLOUR)
-                                .title(songtitle)
-                                .thumbnail(thumblink.unwrap_or(ICON.into()))
-                                .description(statusbar)
-                                .footer(|f| {
-                                    f.text(format!("Duration: {}", duration)).icon_url(ICON)
-                                })
-                        })
-                    })
-                    .await,
-            );
-        } else {
-            check_msg(
-                msg.channel_id
-                    .say(&ctx.http, ":x: there is literally no song playing rn")
-                    .await,
-            );
-        }
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
-                .await,
-        );
+    // the old source is spent, this makes a fresh one from what we know
+    let metadata = track.metadata().clone();
+    if let Err(why) = queue_with_prebuf(source, Some(metadata), ctx, &inv, handler).await {
+        why.report("loop").send(ctx, chan_id).await;
     }
+}
 
-    Ok(())
+/// Sends every song that plays to the end back to the end of the queue
+/// while the queue is looping.
+struct QueueLooper {
+    guild_id: GuildId,
+    chan_id: ChannelId,
+    context: Context,
 }
 
-#[command]
-#[only_in(guilds)]
-async fn play_playlist(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let query = args.raw().collect::<Vec<&str>>().join(" ");
-    if ((query == "") || !(query.starts_with("http"))) && !(query.contains("playlist")) {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Must provide a playlist URL")
-                .await,
-        );
-        return Ok(());
-    }
+#[async_trait]
+impl VoiceEventHandler for QueueLooper {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if !queue_looping(&self.context, self.guild_id).await {
+            return None;
+        }
 
-    check_msg(msg.channel_id.say(&ctx.http, "Polling...").await);
-    let output =
-        tokio::task::spawn_blocking(move || YoutubeDl::new(query).socket_timeout("15").run())
-            .await
-            .unwrap()
-            .unwrap(); // NOTE: DANGER
-    check_msg(msg.channel_id.say(&ctx.http, "Polled!").await);
+        let EventContext::Track(ended) = ctx else {
+            return None;
+        };
 
-    let videos = match output {
-        YoutubeDlOutput::Playlist(playlist) => match playlist.entries {
-            Some(entryvec) => {
-                let mut videos: Vec<String> = Vec::with_capacity(10);
-                for i in entryvec {
-                    videos.push(format!("https://youtube.com/watch?v={}", i.id));
+        let manager = songbird::get(&self.context)
+            .await
+            .expect("Songbird Voice client placed in at initialisation")
+            .clone();
+
+        if let Some(handler_lock) = manager.get(self.guild_id) {
+            let mut handler = handler_lock.lock().await;
+
+            for (state, track) in ended.iter() {
+                // stopped ones were skipped or removed on purpose, and ones
+                // that never got anywhere would just fail again
+                if state.playing == PlayMode::End && !state.position.is_zero() {
+                    requeue(
+                        &self.context,
+                        self.guild_id,
+                        self.chan_id,
+                        &mut handler,
+                        track,
+                    )
+                    .await;
                 }
-                videos
             }
-            None => {
-                async {
-                    check_msg(
-                        msg.channel_id
-                            .say(&ctx.http, "This playlist has no videos!")
-                            .await,
-                    );
-                }
-                .await;
+        }
+
+        None
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+async fn abloop(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let section = match (args.single::<String>(), args.single::<String>()) {
+        (Ok(off), Err(_)) if off == "off" => None,
+        (Ok(start), Ok(end)) => match (parse_timestamp(&start), parse_timestamp(&end)) {
+            (Some(start), Some(end)) => Some((start, end)),
+            _ => {
+                check_msg(
+                    msg.channel_id
+                        .say(&ctx.http, "Times have to look like `1:30` or `1:02:03`")
+                        .await,
+                );
 
                 return Ok(());
             }
         },
         _ => {
-            async {
-                    check_msg(
-                        msg.channel_id
-                            .say(&ctx.http, "THIS ISN'T EVEN A PLAYLIST, istg i filtered it, this shouldn't happen...")
-                            .await,
-                    );
-            }.await;
+            check_msg(
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        "Must provide where the section starts and ends, or `off`",
+                    )
+                    .await,
+            );
 
             return Ok(());
         }
     };
 
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
-        for i in videos {
-            queue_with_prebuf(SongType::Url(i), ctx, msg, &mut handler).await;
-        }
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
-                .await,
-        );
-    }
+    set_ab_loop(ctx, &Invocation::from(msg), section)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
 
     Ok(())
 }
 
-enum SongType {
-    Url(String),
-    Search(String),
+/// The section of a track `~abloop` keeps going back over.
+struct AbLoop;
+
+impl TypeMapKey for AbLoop {
+    type Value = (Duration, Duration);
 }
 
-async fn queue_with_prebuf(
-    song: SongType,
+/// Loops the current song between `section`'s start and end, `None` turns
+/// that off again.
+async fn set_ab_loop(
     ctx: &Context,
-    msg: &Message,
-    handler: &mut Call,
-) -> Option<Metadata> {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+    inv: &Invocation,
+    section: Option<(Duration, Duration)>,
+) -> Result<Reply> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
 
-    match song {
-        SongType::Url(url) => {
-            // NOTE: this is not lazy
-            let source = match Restartable::ytdl(url, false).await {
-                Ok(source) => source,
-                Err(why) => {
-                    match why {
-                        input::error::Error::Json {
-                            ref parsed_text, ..
-                        } => {
-                            if parsed_text == "ERROR: Sign in to confirm your age\n" {
-                                check_msg(
-                                    msg.channel_id
-                                        .say(
-                                            &ctx.http,
-                                            "The video you're trying to play is age-restricted.",
-                                        )
-                                        .await,
-                                );
-                            }
-                        }
-                        _ => {
-                            check_msg(
-                                msg.channel_id
-                                    .say(&ctx.http, "Error sourcing ffmpeg (see console)")
-                                    .await,
-                            );
-                        }
-                    }
-                    println!("Err starting source: {:?}", why);
+    let handler_lock = match manager.get(inv.guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => return Err(VoiceError::NotConnected.into()),
+    };
+    let handler = handler_lock.lock().await;
 
-                    return None;
-                }
+    let current = match handler.queue().current() {
+        Some(current) => current,
+        None => return Ok(Reply::text(":x: there is literally no song playing rn")),
+    };
+
+    let (start, end) = match section {
+        Some(section) => section,
+        None => {
+            return match current.typemap().write().await.remove::<AbLoop>() {
+                Some(_) => Ok(Reply::text("Stopped looping the section")),
+                None => Ok(Reply::text("No section is looping")),
             };
+        }
+    };
 
-            let input: Input = source.into();
+    if start >= end {
+        return Ok(Reply::text("The section has to end after it starts"));
+    }
+    if current
+        .metadata()
+        .duration
+        .is_some_and(|length| end > length)
+    {
+        return Ok(Reply::text("That's past the end of the song"));
+    }
 
-            let metadata = Some(*input.metadata.clone());
+    current
+        .typemap()
+        .write()
+        .await
+        .insert::<AbLoop>((start, end));
+
+    current.add_event(
+        Event::Periodic(Duration::from_millis(250), None),
+        AbLooper {
+            start,
+            end,
+            jumped: AtomicBool::new(false),
+        },
+    )?;
 
-            // This handler object will allow you to, as needed,
-            // control the audio track via events and further commands.
-            handler.enqueue_source(input);
-            if handler.queue().len() < 2 {
-                handler.queue().pause().unwrap();
+    // start practising right away
+    if let Ok(state) = current.get_info().await {
+        if state.position < start || state.position >= end {
+            let _ = current.seek_time(start);
+        }
+    }
 
-                let send_http = ctx.http.clone();
-                let chan_id = msg.channel_id;
+    Ok(Reply::text(format!(
+        "Looping {} to {} of the current song",
+        hrtime::from_sec_padded(start.as_secs()),
+        hrtime::from_sec_padded(end.as_secs())
+    )))
+}
 
-                check_msg(chan_id.say(&ctx.http, "Prebuffering...").await);
+/// Jumps back to the start of the section whenever the track plays past its
+/// end, until the track's [`AbLoop`] changes.
+struct AbLooper {
+    start: Duration,
+    end: Duration,
+    /// Set until the jump shows up in the position, so it only happens once.
+    jumped: AtomicBool,
+}
 
-                let _ = handler.add_global_event(
-                    Event::Delayed(Duration::from_secs(15)),
-                    SongResumer {
-                        guild_id,
-                        context: ctx.clone(),
-                        chan_id,
-                        http: send_http,
-                    },
-                );
+#[async_trait]
+impl VoiceEventHandler for AbLooper {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(&[(state, track)]) = ctx {
+            // turned off, or replaced by another section
+            if track.typemap().read().await.get::<AbLoop>() != Some(&(self.start, self.end)) {
+                return Some(Event::Cancel);
             }
 
-            metadata
-        }
-        SongType::Search(search) => {
-            // NOTE: this is not lazy
-            let source = match Restartable::ytdl_search(search, false).await {
-                Ok(source) => source,
-                Err(why) => {
-                    println!("Err starting source: {:?}", why);
-
-                    check_msg(
-                        msg.channel_id
-                            .say(&ctx.http, "Error sourcing ffmpeg (see console)")
-                            .await,
-                    );
-
-                    return None;
+            if state.position >= self.end {
+                if !self.jumped.swap(true, Ordering::Relaxed) {
+                    let _ = track.seek_time(self.start);
                 }
-            };
-
-            let input: Input = source.into();
-
-            let metadata = Some(*input.metadata.clone());
-
-            handler.enqueue_source(input);
-            if handler.queue().len() < 2 {
-                handler.queue().pause().unwrap();
-
-                let send_http = ctx.http.clone();
-                let chan_id = msg.channel_id;
-
-                check_msg(chan_id.say(&ctx.http, "Prebuffering...").await);
-
-                let _ = handler.add_global_event(
-                    Event::Delayed(Duration::from_secs(15)),
-                    SongResumer {
-                        guild_id,
-                        context: ctx.clone(),
-                        chan_id,
-                        http: send_http,
-                    },
-                );
+            } else {
+                self.jumped.store(false, Ordering::Relaxed);
             }
-
-            metadata
         }
+
+        None
     }
 }
 
 #[command]
 #[only_in(guilds)]
-async fn play(ctx: &Context, msg: &Message, /*mut*/ args: Args) -> CommandResult {
-    let query = args.raw().collect::<Vec<&str>>().join(" ");
-    if query == "" {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Must provide a URL or a search query")
-                .await,
-        );
+#[aliases("np")]
+async fn nowplaying(ctx: &Context, msg: &Message) -> CommandResult {
+    now_playing(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
 
-        return Ok(());
-    }
+    Ok(())
+}
 
-    let guild = msg.guild(&ctx.cache).await.unwrap();
+async fn now_playing(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild = inv
+        .guild_id
+        .to_guild_cached(&ctx.cache)
+        .await
+        .ok_or(AoedeError::GuildUnavailable)?;
     let guild_id = guild.id;
 
     let manager = songbird::get(ctx)
@@ -739,140 +1144,1916 @@ async fn play(ctx: &Context, msg: &Message, /*mut*/ args: Args) -> CommandResult
         .expect("Songbird Voice client placed in at initialisation.")
         .clone();
 
+    let channel_id = guild
+        .voice_states
+        .get(&inv.author)
+        .and_then(|voice_state| voice_state.channel_id);
+
+    let chan = match channel_id {
+        Some(channel) => channel,
+        None => return Ok(Reply::text("You are not in a vc.")),
+    };
+
     if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
+        let handler = handler_lock.lock().await;
+
+        let songtitle;
+        let thumblink;
+        let statusbar;
+        let duration;
+
+        let mut np_str = String::new();
 
-        let mut metadata: Option<Metadata> = None;
+        if let Some(current) = handler.queue().current() {
+            let md = current.metadata().clone();
+            songtitle = md.title.unwrap_or("<no title>".into());
+            thumblink = md.thumbnail;
+            let state = current.get_info().await?;
 
-        if query.starts_with("http") {
-            let url;
-            if query.find(" ").is_some() {
-                url = query[0..query.find(" ").unwrap() + 1].to_string();
+            let curpos = state.position;
+
+            if state.playing == PlayMode::Pause {
+                np_str.push_str(":pause_button: ");
             } else {
-                url = query.to_string();
+                np_str.push_str(":arrow_forward: ");
             }
 
-            metadata = match queue_with_prebuf(SongType::Url(url), ctx, msg, &mut handler).await {
-                None => return Ok(()),
-                Some(m) => Some(m),
+            // streams and plenty of local files don't say how long they are
+            duration = match md.duration {
+                Some(length) => {
+                    np_str.push_str(&progress_bar(curpos, length));
+                    np_str.push(' ');
+
+                    format!(
+                        "[{}/{}]",
+                        hrtime::from_sec_padded(curpos.as_secs()),
+                        hrtime::from_sec_padded(length.as_secs())
+                    )
+                }
+                None => format!("[{}/live]", hrtime::from_sec_padded(curpos.as_secs())),
+            };
+
+            np_str.push_str(&format!(":loud_sound: {}%", (state.volume * 100.).round()));
+
+            statusbar = np_str;
+
+            let mut looping = loop_mode(ctx, guild_id, &handler).await.to_string();
+            if let Some((start, end)) = current.typemap().read().await.get::<AbLoop>() {
+                looping.push_str(&format!(
+                    ", section {}-{}",
+                    hrtime::from_sec_padded(start.as_secs()),
+                    hrtime::from_sec_padded(end.as_secs())
+                ));
             }
+
+            Ok(Reply::embed(
+                Some(format!("Now playing (in {}): ", chan.mention())),
+                |e| {
+                    e.colour(embed_colour())
+                        .title(songtitle)
+                        .thumbnail(thumblink.unwrap_or(icon().into()))
+                        .description(statusbar)
+                        .footer(|f| {
+                            f.text(format!("Duration: {} | Loop: {}", duration, looping))
+                                .icon_url(icon())
+                        })
+                },
+            ))
         } else {
-            metadata =
-                match queue_with_prebuf(SongType::Search(query), ctx, msg, &mut handler).await {
-                    None => return Ok(()),
-                    Some(m) => Some(m),
-                }
+            Ok(Reply::text(":x: there is literally no song playing rn"))
         }
-
-        check_msg(
-            msg.channel_id
-                .send_message(&ctx.http, |m| {
-                    m.embed(|e| {
-                        if metadata.is_none() {
-                            e.title("there was no metadata")
-                                .description("how the fuck did this happen/")
-                        } else {
-                            let metadata = metadata.unwrap(); // safe to unwrap because we checked, i hope i dont regret this
-
-                            e.colour(EMBED_COLOUR)
-                                .title(metadata.title.unwrap_or("<no title> (how?????????)".into()))
-                                .thumbnail(metadata.thumbnail.unwrap_or(ICON.into()))
-                                .description(format!(
-                                    "Added song to queue, position `{}`",
-                                    handler.queue().len()
-                                ))
-                                .footer(|f| {
-                                    f.text(format!(
-                                        "Duration: {}",
-                                        hrtime::from_sec_padded(
-                                            metadata
-                                                .duration
-                                                .unwrap_or(Duration::from_secs(0))
-                                                .as_secs()
-                                        )
-                                    ))
-                                    .icon_url(ICON)
-                                })
-                        }
-                    })
-                })
-                .await,
-        );
     } else {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
-                .await,
-        );
+        Err(VoiceError::NotConnected.into())
     }
-
-    Ok(())
 }
 
-struct SongResumer {
-    guild_id: GuildId,
-    context: Context,
-    chan_id: ChannelId,
-    http: Arc<Http>,
-}
+/// The `▬▬:radio_button:▬▬` bar of how far into a song `position` is.
+fn progress_bar(position: Duration, length: Duration) -> String {
+    let mut bar = String::new();
 
-#[async_trait]
-impl VoiceEventHandler for SongResumer {
-    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
-        if let EventContext::Track(&[(state, track)]) = ctx {
-            let manager = songbird::get(&self.context)
-                .await
-                .expect("Songbird Voice client placed in at initialisation")
-                .clone();
-            println!("reached");
+    // 13 dynamic symbols
+    // formula: (current seconds/duration seconds)*13
+    // current point is the round up of formula, fill the rest
+    let current_pointer = ((position.as_secs_f64() / length.as_secs_f64()) * 13.).ceil();
 
-            if let Some(handler_lock) = manager.get(self.guild_id) {
-                let handler = handler_lock.lock().await;
-                handler.queue().resume().unwrap();
-                Some(Event::Cancel)
-            } else {
-                // i do not know kiel je fek this could happen but here goes
-                check_msg(
-                    self.chan_id
-                        .say(&self.http, "Not in a voice channel to play in")
-                        .await,
-                );
-                None
-            }
-        } else {
-            None
-        }
+    let before = current_pointer - 1.;
+    let after = 13. - current_pointer;
+
+    for _ in 0..=before as u64 {
+        bar.push('▬');
+    }
+
+    // the current pointer
+    bar.push_str(":radio_button:");
+
+    for _ in 0..=after as u64 {
+        bar.push('▬');
     }
+
+    bar
 }
 
 #[command]
 #[only_in(guilds)]
-async fn play_fade(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let url = match args.single::<String>() {
-        Ok(url) => url,
-        Err(_) => {
-            check_msg(
-                msg.channel_id
-                    .say(&ctx.http, "Must provide a URL to a video or audio")
-                    .await,
-            );
+async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
+    set_paused(ctx, &Invocation::from(msg), Some(true))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
 
-            return Ok(());
-        }
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("unpause")]
+async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
+    set_paused(ctx, &Invocation::from(msg), Some(false))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn toggle(ctx: &Context, msg: &Message) -> CommandResult {
+    set_paused(ctx, &Invocation::from(msg), None)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+/// Pauses or resumes the queue, `None` flips whatever it is now.
+async fn set_paused(ctx: &Context, inv: &Invocation, pause: Option<bool>) -> Result<Reply> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let handler_lock = match manager.get(inv.guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => return Err(VoiceError::NotConnected.into()),
+    };
+    let handler = handler_lock.lock().await;
+    let queue = handler.queue();
+
+    let current = match queue.current() {
+        Some(current) => current,
+        None => return Ok(Reply::text(":x: there is literally no song playing rn")),
+    };
+
+    let paused = match current.get_info().await {
+        Ok(state) => state.playing == PlayMode::Pause,
+        Err(_) => return Ok(Reply::text("The song is already finished")),
+    };
+
+    let pause = pause.unwrap_or(!paused);
+    if !pause && !paused {
+        return Ok(Reply::text("Already playing"));
+    }
+    // paused for prebuffering still needs marking, or it'd start on its own
+    if pause && paused && paused_by_user(&current).await {
+        return Ok(Reply::text("Already paused"));
+    }
+
+    if pause {
+        queue.pause()?;
+    } else {
+        queue.resume()?;
+    }
+
+    current
+        .typemap()
+        .write()
+        .await
+        .insert::<PausedByUser>(pause);
+
+    Ok(Reply::text(if pause {
+        ":pause_button: Paused"
+    } else {
+        ":arrow_forward: Resumed"
+    }))
+}
+
+async fn paused_by_user(track: &TrackHandle) -> bool {
+    track
+        .typemap()
+        .read()
+        .await
+        .get::<PausedByUser>()
+        .copied()
+        .unwrap_or(false)
+}
+
+#[command]
+#[only_in(guilds)]
+async fn seek(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let position = match args
+        .single::<String>()
+        .ok()
+        .and_then(|t| parse_timestamp(&t))
+    {
+        Some(position) => position,
+        None => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must provide a time like `1:30` or `1:02:03`")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    seek_song(ctx, &Invocation::from(msg), Seek::To(position))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("ff")]
+async fn forward(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let secs = match args.single::<u64>() {
+        Ok(secs) => secs,
+        Err(_) => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must provide how many seconds to skip ahead")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    seek_song(
+        ctx,
+        &Invocation::from(msg),
+        Seek::Forward(Duration::from_secs(secs)),
+    )
+    .await?
+    .send(ctx, msg.channel_id)
+    .await;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("rw")]
+async fn rewind(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let secs = match args.single::<u64>() {
+        Ok(secs) => secs,
+        Err(_) => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must provide how many seconds to go back")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    seek_song(
+        ctx,
+        &Invocation::from(msg),
+        Seek::Back(Duration::from_secs(secs)),
+    )
+    .await?
+    .send(ctx, msg.channel_id)
+    .await;
+
+    Ok(())
+}
+
+/// Where `~seek`, `~forward` and `~rewind` go in the current song.
+#[derive(Clone, Copy, Debug)]
+enum Seek {
+    To(Duration),
+    Forward(Duration),
+    Back(Duration),
+}
+
+/// Parses `ss`, `mm:ss` or `hh:mm:ss`.
+fn parse_timestamp(text: &str) -> Option<Duration> {
+    let parts = text
+        .split(':')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    let secs = match parts[..] {
+        [s] => s,
+        [m, s] if s < 60 => m.checked_mul(60)?.checked_add(s)?,
+        [h, m, s] if m < 60 && s < 60 => h.checked_mul(60 * 60)?.checked_add(m * 60 + s)?,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(secs))
+}
+
+async fn seek_song(ctx: &Context, inv: &Invocation, seek: Seek) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let handler_lock = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => return Err(VoiceError::NotConnected.into()),
+    };
+    let handler = handler_lock.lock().await;
+
+    let current = match handler.queue().current() {
+        Some(current) => current,
+        None => return Ok(Reply::text(":x: there is literally no song playing rn")),
+    };
+
+    let length = match current.metadata().duration {
+        Some(length) => length,
+        None => {
+            return Ok(Reply::text(
+                "This song doesn't say how long it is, so it can't be seeked",
+            ))
+        }
+    };
+
+    let position = match current.get_info().await {
+        Ok(state) => state.position,
+        Err(_) => return Ok(Reply::text("The song is already finished")),
+    };
+
+    let target = match seek {
+        Seek::To(target) => target,
+        Seek::Forward(by) => position.saturating_add(by),
+        Seek::Back(by) => position.saturating_sub(by),
+    };
+
+    if target >= length {
+        return Ok(Reply::text(format!(
+            "That's past the end of the song, it's only {} long",
+            hrtime::from_sec_padded(length.as_secs())
+        )));
+    }
+
+    current.seek_time(target)?;
+
+    let md = current.metadata().clone();
+
+    Ok(Reply::embed(None, |e| {
+        e.colour(embed_colour())
+            .title(md.title.unwrap_or("<no title>".into()))
+            .thumbnail(md.thumbnail.unwrap_or(icon().into()))
+            .description(format!(":fast_forward: {}", progress_bar(target, length)))
+            .footer(|f| {
+                f.text(format!(
+                    "Jumped to [{}/{}]",
+                    hrtime::from_sec_padded(target.as_secs()),
+                    hrtime::from_sec_padded(length.as_secs())
+                ))
+                .icon_url(icon())
+            })
+    }))
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("vol")]
+async fn volume(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let volume = if args.is_empty() {
+        None
+    } else {
+        match args.single::<u16>() {
+            Ok(volume) => Some(volume),
+            Err(_) => {
+                check_msg(
+                    msg.channel_id
+                        .say(
+                            &ctx.http,
+                            format!(
+                                "The volume has to be a number from 0 to {}",
+                                config::MAX_VOLUME
+                            ),
+                        )
+                        .await,
+                );
+
+                return Ok(());
+            }
+        }
+    };
+
+    change_volume(ctx, &Invocation::from(msg), volume)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+/// Sets the volume of everything queued and makes it the guild's default,
+/// without a volume just says what it is.
+async fn change_volume(ctx: &Context, inv: &Invocation, volume: Option<u16>) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let volume = match volume {
+        Some(volume) if volume > config::MAX_VOLUME => {
+            return Ok(Reply::text(format!(
+                "The volume has to be a number from 0 to {}",
+                config::MAX_VOLUME
+            )))
+        }
+        Some(volume) => volume,
+        None => {
+            return Ok(Reply::text(format!(
+                "The volume is at {}%",
+                guild_settings(ctx, guild_id).await.volume()
+            )))
+        }
+    };
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        for track in handler.queue().current_queue() {
+            let _ = track.set_volume(volume as f32 / 100.);
+        }
+    }
+
+    let saved = update_guild_settings(ctx, guild_id, |s| {
+        s.volume = if volume == config::get().default_volume {
+            None
+        } else {
+            Some(volume)
+        };
+    })
+    .await;
+
+    match saved {
+        Ok(_) => Ok(Reply::text(format!("Volume set to {}%", volume))),
+        Err(why) => {
+            tracing::error!("Couldn't save settings: {:?}", why);
+
+            Ok(Reply::text(format!(
+                "Volume set to {}%, but it couldn't be saved for the next songs (see console)",
+                volume
+            )))
+        }
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+async fn play_playlist(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (query, options) = match PlaylistOptions::parse(args.rest()) {
+        Ok(parsed) => parsed,
+        Err(why) => {
+            check_msg(msg.channel_id.say(&ctx.http, why).await);
+
+            return Ok(());
+        }
+    };
+
+    enqueue_playlist(ctx, &Invocation::from(msg), query, options)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+/// Which entries of a playlist to import, and in what order.
+#[derive(Clone, Copy, Debug, Default)]
+struct PlaylistOptions {
+    /// Entries counted from 1, both ends included.
+    range: Option<(usize, usize)>,
+    reverse: bool,
+    shuffle: bool,
+    limit: Option<usize>,
+}
+
+impl PlaylistOptions {
+    /// Splits `<url> [start-end] [--reverse] [--shuffle] [--limit N]` into
+    /// the URL and the options.
+    fn parse(args: &str) -> Result<(String, Self), String> {
+        let mut options = Self::default();
+        let mut query = Vec::new();
+
+        let mut args = args.split_whitespace();
+        while let Some(arg) = args.next() {
+            match arg {
+                "--reverse" => options.reverse = true,
+                "--shuffle" => options.shuffle = true,
+                "--limit" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n > 0 => options.limit = Some(n),
+                    _ => return Err("`--limit` needs a number of songs after it".into()),
+                },
+                _ if !query.is_empty() && arg.starts_with(|c: char| c.is_ascii_digit()) => {
+                    options.range = Some(Self::parse_range(arg)?)
+                }
+                _ => query.push(arg),
+            }
+        }
+
+        Ok((query.join(" "), options))
+    }
+
+    /// Parses `start-end`, or a single entry.
+    fn parse_range(text: &str) -> Result<(usize, usize), String> {
+        match parse_range(text) {
+            Some((from, to)) if from >= 1 && from <= to => Ok((from, to)),
+            _ => Err(format!(
+                "`{}` isn't a range of entries, they look like `3-10`",
+                text
+            )),
+        }
+    }
+
+    /// The first and last entry youtube-dl has to look up for `apply` to
+    /// pick from, `None` being the end of the playlist. Big playlists would
+    /// otherwise be looked up in full just to import a few of their songs.
+    fn span(&self, max: usize) -> (usize, Option<usize>) {
+        let (from, to) = match self.range {
+            Some((from, to)) => (from, Some(to)),
+            None => (1, None),
+        };
+
+        // reversed or shuffled picks can come from anywhere in the range
+        if self.reverse || self.shuffle {
+            return (from, to);
+        }
+
+        // one past the cap lets `apply` tell that the cap cut something off
+        let count = self.limit.unwrap_or(usize::MAX).min(max.saturating_add(1));
+        let end = from.saturating_add(count.saturating_sub(1));
+
+        (from, Some(to.map_or(end, |to| to.min(end))))
+    }
+
+    /// Picks the entries to import out of the whole `playlist`, and whether
+    /// `max` cut off some of what the options would have let through.
+    fn apply<T>(&self, playlist: Vec<T>, max: usize) -> (Vec<T>, bool) {
+        let (from, to) = self.range.unwrap_or((1, playlist.len()));
+        // ends past the playlist just mean up to its end
+        let (from, to) = (from.max(1), to.min(playlist.len()));
+        let mut entries = playlist
+            .into_iter()
+            .skip(from - 1)
+            .take((to + 1).saturating_sub(from))
+            .collect::<Vec<_>>();
+
+        if self.reverse {
+            entries.reverse();
+        }
+        if self.shuffle {
+            entries.shuffle(&mut rand::thread_rng());
+        }
+
+        let wanted = self.limit.unwrap_or(usize::MAX).min(entries.len());
+        entries.truncate(wanted.min(max));
+
+        (entries, wanted > max)
+    }
+}
+
+async fn enqueue_playlist(
+    ctx: &Context,
+    inv: &Invocation,
+    query: String,
+    options: PlaylistOptions,
+) -> Result<Reply> {
+    // whatever youtube-dl makes of it decides if it's a playlist
+    if !query.starts_with("http") {
+        return Ok(Reply::text("Must provide a playlist URL"));
+    }
+
+    // nobody has to ~join first
+    join_if_needed(ctx, inv).await?;
+
+    let max = guild_settings(ctx, inv.guild_id).await.max_playlist_size();
+    let (start, end) = options.span(max);
+
+    check_msg(inv.channel_id.say(&ctx.http, "Polling...").await);
+    let socket_timeout = config::get().ytdl_socket_timeout.to_string();
+    let url = query.clone();
+    let output = tokio::task::spawn_blocking(move || {
+        let mut ytdl = YoutubeDl::new(url);
+        ytdl.socket_timeout(socket_timeout)
+            .extra_arg("--playlist-start")
+            .extra_arg(start.to_string());
+        if let Some(end) = end {
+            ytdl.extra_arg("--playlist-end").extra_arg(end.to_string());
+        }
+        ytdl.run()
+    })
+    .await??;
+
+    let entryvec = match output {
+        YoutubeDlOutput::Playlist(playlist) => match playlist.entries {
+            Some(entryvec) if !entryvec.is_empty() => entryvec,
+            _ if options.range.is_some() => {
+                return Ok(Reply::text(format!(
+                    "The playlist doesn't have an entry {}",
+                    start
+                )))
+            }
+            _ => return Ok(Reply::text("This playlist has no videos!")),
+        },
+        // a single song is just a very short playlist
+        YoutubeDlOutput::SingleVideo(video) => vec![*video],
+    };
+    check_msg(inv.channel_id.say(&ctx.http, "Polled!").await);
+
+    // youtube-dl already cut the range out
+    let (entryvec, capped) = PlaylistOptions {
+        range: None,
+        ..options
+    }
+    .apply(entryvec, max);
+
+    let mut videos: Vec<(String, Metadata)> = Vec::with_capacity(10);
+    for i in entryvec {
+        let url = match entry_url(&i) {
+            Some(url) => url,
+            None => {
+                tracing::warn!("Skipping playlist entry {} without a URL", i.id);
+                continue;
+            }
+        };
+
+        // the playlist already told us everything, no need to ask again per song
+        let metadata = Metadata::from_ytdl_output(serde_json::to_value(&i).unwrap_or_default());
+        videos.push((url, metadata));
+    }
+
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+        let picked = videos.len();
+        let mut queued = 0;
+        for (url, metadata) in videos {
+            let song = Song::new(Ytdl::NAME, url);
+            match queue_with_prebuf(song, Some(metadata), ctx, inv, &mut handler).await {
+                Ok(_) => queued += 1,
+                Err(why) => why.report("play_playlist").send(ctx, inv.channel_id).await,
+            }
+        }
+
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        let mut summary = format!("Added {}/{} songs to the queue", queued, picked);
+        if capped {
+            summary.push_str(&format!(" (this server imports at most {} at once)", max));
+        }
+
+        Ok(Reply::text(summary))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+/// Where a playlist or search entry can be sourced from again on its own.
+fn entry_url(video: &SingleVideo) -> Option<String> {
+    if let Some(url) = &video.webpage_url {
+        return Some(url.clone());
+    }
+
+    // flat entries have the page in `url`, full ones the media itself
+    if let Some(url) = video.url.as_ref().filter(|url| url.starts_with("http")) {
+        return Some(url.clone());
+    }
+
+    // youtube sometimes gives nothing but the id
+    let extractor = video
+        .extractor_key
+        .as_deref()
+        .or(video.extractor.as_deref())
+        .unwrap_or_default();
+    extractor
+        .to_lowercase()
+        .starts_with("youtube")
+        .then(|| format!("https://youtube.com/watch?v={}", video.id))
+}
+
+/// The user who queued a track, kept in the track's own typemap.
+struct Requester;
+
+impl TypeMapKey for Requester {
+    type Value = UserId;
+}
+
+/// What a track was sourced from, so it can be sourced again after a restart.
+struct Source;
+
+impl TypeMapKey for Source {
+    type Value = Song;
+}
+
+/// Whether a track was paused with `~pause`, so the prebuffering doesn't
+/// start it again behind the user's back.
+struct PausedByUser;
+
+impl TypeMapKey for PausedByUser {
+    type Value = bool;
+}
+
+async fn resolvers(ctx: &Context) -> Arc<Resolvers> {
+    ctx.data
+        .read()
+        .await
+        .get::<Resolvers>()
+        .cloned()
+        .expect("Resolvers placed in at initialisation.")
+}
+
+/// Sources and enqueues a song, on failure returns what should be said about it.
+///
+/// Nothing gets streamed until the song is close to playing, `known` metadata
+/// lets its resolver skip looking it up again.
+async fn queue_with_prebuf(
+    song: Song,
+    known: Option<Metadata>,
+    ctx: &Context,
+    inv: &Invocation,
+    handler: &mut Call,
+) -> Result<Metadata> {
+    let guild_id = inv.guild_id;
+
+    let resolvers = resolvers(ctx).await;
+    let resolver = resolvers.get(&song.resolver).ok_or_else(|| {
+        SourceError::NotFound(format!(
+            "Don't know how to play `{}` anymore, there's no `{}` source",
+            song.query, song.resolver
+        ))
+    })?;
+
+    let Resolved {
+        mut input,
+        metadata,
+        buffered,
+        song,
+    } = resolver.resolve(&song.query, known).await?;
+    *input.metadata = metadata.clone();
+
+    // This handler object will allow you to, as needed,
+    // control the audio track via events and further commands.
+    let (mut track, handle) = tracks::create_player(input);
+    track.set_volume(guild_settings(ctx, guild_id).await.volume() as f32 / 100.);
+    resolver
+        .track_added(ctx, guild_id, &handle, &song.query)
+        .await;
+    {
+        let mut typemap = handle.typemap().write().await;
+        typemap.insert::<Requester>(inv.author);
+        typemap.insert::<Source>(song);
+    }
+    handler.enqueue(track);
+    lazy::preload(handler);
+    idle::cancel(ctx, guild_id, idle::Reason::Idle).await;
+
+    // the first song waits until it has something to play, the rest get
+    // preloaded while the ones before them play
+    if handler.queue().len() < 2 {
+        handler.queue().pause()?;
+
+        check_msg(inv.channel_id.say(&ctx.http, "Prebuffering...").await);
+
+        tokio::spawn(resume_when_buffered(
+            ctx.clone(),
+            guild_id,
+            inv.channel_id,
+            buffered,
+        ));
+    }
+
+    Ok(metadata)
+}
+
+#[command]
+#[only_in(guilds)]
+async fn play(ctx: &Context, msg: &Message, /*mut*/ args: Args) -> CommandResult {
+    let query = args.raw().collect::<Vec<&str>>().join(" ");
+    let inv = Invocation::from(msg);
+
+    // uploads go after whatever was asked for by name
+    if !query.is_empty() || msg.attachments.is_empty() {
+        enqueue_song(ctx, &inv, query)
+            .await?
+            .send(ctx, msg.channel_id)
+            .await;
+    }
+    if !msg.attachments.is_empty() {
+        enqueue_attachments(ctx, &inv, &msg.attachments)
+            .await?
+            .send(ctx, msg.channel_id)
+            .await;
+    }
+
+    Ok(())
+}
+
+/// Downloads and queues every audio file uploaded with the command.
+async fn enqueue_attachments(
+    ctx: &Context,
+    inv: &Invocation,
+    uploads: &[Attachment],
+) -> Result<Reply> {
+    // nobody has to ~join first
+    join_if_needed(ctx, inv).await?;
+
+    // the downloads are deleted once these go, unless a track holds on to them
+    let mut downloads = Vec::with_capacity(uploads.len());
+    let mut songs = Vec::with_capacity(uploads.len());
+    for upload in uploads {
+        match attachments::download(upload).await {
+            Ok(file) => {
+                songs.push((
+                    Song::new(AttachmentResolver::NAME, file.path().to_string_lossy()),
+                    Metadata {
+                        title: Some(upload.filename.clone()),
+                        ..Default::default()
+                    },
+                ));
+                downloads.push(file);
+            }
+            Err(why) => why.report("play").send(ctx, inv.channel_id).await,
+        }
+    }
+
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+        let mut queued = 0;
+        for (song, known) in songs {
+            match queue_with_prebuf(song, Some(known), ctx, inv, &mut handler).await {
+                Ok(_) => queued += 1,
+                Err(why) => why.report("play").send(ctx, inv.channel_id).await,
+            }
+        }
+
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::text(format!(
+            "Added {}/{} attachments to the queue",
+            queued,
+            uploads.len()
+        )))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+async fn enqueue_song(ctx: &Context, inv: &Invocation, query: String) -> Result<Reply> {
+    if query == "" {
+        return Ok(Reply::text("Must provide a URL or a search query"));
+    }
+
+    let song = match resolvers(ctx).await.for_query(&query) {
+        Some(resolver) => Song::new(resolver.name(), query),
+        None => return Ok(Reply::text("Don't know how to play that")),
+    };
+
+    enqueue(ctx, inv, song, None).await
+}
+
+#[command]
+#[only_in(guilds)]
+async fn search(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let query = args.raw().collect::<Vec<&str>>().join(" ");
+
+    pick_search_result(ctx, &Invocation::from(msg), query)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+/// Lists the top youtube results for `query` and queues whichever one the
+/// caller picks by replying with its number.
+async fn pick_search_result(ctx: &Context, inv: &Invocation, query: String) -> Result<Reply> {
+    if query.is_empty() {
+        return Ok(Reply::text("Must provide a search query"));
+    }
+
+    let config = config::get();
+
+    check_msg(inv.channel_id.say(&ctx.http, "Searching...").await);
+    let options = SearchOptions::youtube(query.clone()).with_count(config.search_results);
+    let socket_timeout = config.ytdl_socket_timeout.to_string();
+    let output = tokio::task::spawn_blocking(move || {
+        YoutubeDl::search_for(&options)
+            .socket_timeout(socket_timeout)
+            .run()
+    })
+    .await??;
+
+    let videos = match output {
+        YoutubeDlOutput::Playlist(playlist) => playlist.entries.unwrap_or_default(),
+        YoutubeDlOutput::SingleVideo(video) => vec![*video],
+    };
+
+    if videos.is_empty() {
+        return Ok(Reply::text(format!("Nothing found for `{}`", query)));
+    }
+
+    // the search already told us everything, no need to ask again for the pick
+    let mut results = videos
+        .into_iter()
+        .map(|video| {
+            let url = entry_url(&video)
+                .unwrap_or_else(|| format!("https://youtube.com/watch?v={}", video.id));
+            let uploader = video.uploader.clone().unwrap_or("<unknown>".into());
+            let metadata =
+                Metadata::from_ytdl_output(serde_json::to_value(&video).unwrap_or_default());
+
+            (url, uploader, metadata)
+        })
+        .collect::<Vec<_>>();
+
+    let mut list = String::new();
+    for (i, (_, uploader, metadata)) in results.iter().enumerate() {
+        list.push_str(&format!(
+            "`{}.` {} `[{}]` by {}\n",
+            i + 1,
+            metadata.title.as_deref().unwrap_or("<no title>"),
+            hrtime::from_sec_padded(metadata.duration.unwrap_or_default().as_secs()),
+            uploader
+        ));
+    }
+
+    Reply::embed(None, |e| {
+        e.colour(embed_colour())
+            .title(format!("Results for \"{}\"", query))
+            .description(list)
+            .footer(|f| {
+                f.text(format!(
+                    "Reply with a number to queue it, or \"cancel\", within {}s",
+                    config.search_timeout_secs
+                ))
+                .icon_url(icon())
+            })
+    })
+    .send(ctx, inv.channel_id)
+    .await;
+
+    let count = results.len();
+    let reply = inv
+        .channel_id
+        .await_reply(ctx)
+        .author_id(inv.author)
+        .filter(move |reply| {
+            let content = reply.content.trim();
+            content.eq_ignore_ascii_case("cancel")
+                || content
+                    .parse::<usize>()
+                    .is_ok_and(|n| (1..=count).contains(&n))
+        })
+        .timeout(Duration::from_secs(config.search_timeout_secs))
+        .await;
+
+    let picked = match reply.and_then(|reply| reply.content.trim().parse::<usize>().ok()) {
+        Some(n) => n,
+        None => return Ok(Reply::text("Search closed, nothing queued")),
+    };
+
+    let (url, _, metadata) = results.swap_remove(picked - 1);
+    enqueue(ctx, inv, Song::new(Ytdl::NAME, url), Some(metadata)).await
+}
+
+#[command]
+#[only_in(guilds)]
+async fn local(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let query = args.raw().collect::<Vec<&str>>().join(" ");
+
+    enqueue_local(ctx, &Invocation::from(msg), query)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+/// Plays a file from the music library, by its path or a search of the names.
+async fn enqueue_local(ctx: &Context, inv: &Invocation, query: String) -> Result<Reply> {
+    if library::root().is_none() {
+        return Ok(Reply::text("There's no music library set up"));
+    }
+    if query.is_empty() {
+        return Ok(Reply::text("Must provide a file path or a search query"));
+    }
+
+    let search = query.clone();
+    let found = tokio::task::spawn_blocking(move || library::find(&search)).await??;
+
+    let relative = match found.as_slice() {
+        [] => {
+            return Ok(Reply::text(format!(
+                "Nothing in the music library matches `{}`",
+                query
+            )))
+        }
+        [file] => file.clone(),
+        files => {
+            let mut list = String::new();
+            for file in files.iter().take(10) {
+                list.push_str(&format!("- `{}`\n", file.display()));
+            }
+            if files.len() > 10 {
+                list.push_str(&format!("...and {} more\n", files.len() - 10));
+            }
+
+            return Ok(Reply::text(format!(
+                "{} files match `{}`, be more specific:\n{}",
+                files.len(),
+                query,
+                list
+            )));
+        }
+    };
+
+    let song = Song::new(LocalResolver::NAME, relative.to_string_lossy());
+    enqueue(ctx, inv, song, None).await
+}
+
+/// Queues a single song, joining the caller's channel if needed.
+async fn enqueue(
+    ctx: &Context,
+    inv: &Invocation,
+    song: Song,
+    known: Option<Metadata>,
+) -> Result<Reply> {
+    // nobody has to ~join first
+    join_if_needed(ctx, inv).await?;
+
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+
+        let metadata = queue_with_prebuf(song, known, ctx, inv, &mut handler).await?;
+
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::embed(None, |e| {
+            e.colour(embed_colour())
+                .title(metadata.title.unwrap_or("<no title> (how?????????)".into()))
+                .thumbnail(metadata.thumbnail.unwrap_or(icon().into()))
+                .description(format!(
+                    "Added song to queue, position `{}`",
+                    handler.queue().len()
+                ))
+                .footer(|f| {
+                    f.text(format!(
+                        "Duration: {}",
+                        hrtime::from_sec_padded(
+                            metadata
+                                .duration
+                                .unwrap_or(Duration::from_secs(0))
+                                .as_secs()
+                        )
+                    ))
+                    .icon_url(icon())
+                })
+        }))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+/// Unpauses the queue once its first song has buffered, or once it's clear
+/// it won't, saying which it was.
+async fn resume_when_buffered(
+    ctx: Context,
+    guild_id: GuildId,
+    chan_id: ChannelId,
+    buffered: lazy::Buffered,
+) {
+    let started = Instant::now();
+    let timeout = Duration::from_secs(config::get().prebuffer_timeout_secs);
+
+    let msg = match tokio::time::timeout(timeout, buffered).await {
+        Ok(Ok(true)) => format!("Prebuffered in {:.1}s", started.elapsed().as_secs_f64()),
+        Ok(_) => "Couldn't buffer the song, it might not play (see console)".to_string(),
+        Err(_) => format!(
+            "Still buffering after {}s, playing anyway",
+            timeout.as_secs()
+        ),
+    };
+    check_msg(chan_id.say(&ctx.http, msg).await);
+
+    let manager = songbird::get(&ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        // someone hit ~pause while we were buffering, that's theirs to undo
+        if let Some(current) = handler.queue().current() {
+            if paused_by_user(&current).await {
+                return;
+            }
+        }
+
+        let _ = handler.queue().resume();
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+async fn play_fade(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let url = match args.single::<String>() {
+        Ok(url) => url,
+        Err(_) => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must provide a URL to a video or audio")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    play_fading(ctx, &Invocation::from(msg), url)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn play_fading(ctx: &Context, inv: &Invocation, url: String) -> Result<Reply> {
+    if !url.starts_with("http") {
+        return Ok(Reply::text("Must provide a valid URL"));
+    }
+
+    // nobody has to ~join first
+    join_if_needed(ctx, inv).await?;
+
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+
+        let source = input::ytdl(&url).await?;
+
+        // This handler object will allow you to, as needed,
+        // control the audio track via events and further commands.
+        let song = handler.play_source(source);
+        let send_http = ctx.http.clone();
+        let chan_id = inv.channel_id;
+
+        // This shows how to periodically fire an event, in this case to
+        // periodically make a track quieter until it can be no longer heard.
+        let _ = song.add_event(
+            Event::Periodic(Duration::from_secs(5), Some(Duration::from_secs(7))),
+            SongFader {
+                chan_id,
+                http: send_http,
+            },
+        );
+
+        let send_http = ctx.http.clone();
+
+        // This shows how to fire an event once an audio track completes,
+        // either due to hitting the end of the bytestream or stopped by user code.
+        let _ = song.add_event(
+            Event::Track(TrackEvent::End),
+            SongEndNotifier {
+                chan_id,
+                http: send_http,
+            },
+        );
+
+        Ok(Reply::text("Playing song"))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+struct SongFader {
+    chan_id: ChannelId,
+    http: Arc<Http>,
+}
+
+#[async_trait]
+impl VoiceEventHandler for SongFader {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(&[(state, track)]) = ctx {
+            let _ = track.set_volume(state.volume / 2.0);
+
+            if state.volume < 1e-2 {
+                let _ = track.stop();
+                check_msg(self.chan_id.say(&self.http, "Stopping song...").await);
+                Some(Event::Cancel)
+            } else {
+                check_msg(self.chan_id.say(&self.http, "Volume reduced.").await);
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
+
+struct SongEndNotifier {
+    chan_id: ChannelId,
+    http: Arc<Http>,
+}
+
+#[async_trait]
+impl VoiceEventHandler for SongEndNotifier {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        check_msg(
+            self.chan_id
+                .say(&self.http, "Song faded out completely!")
+                .await,
+        );
+
+        None
+    }
+}
+
+/// How many entries a single `~queue` page shows.
+const QUEUE_PAGE_SIZE: usize = 10;
+
+#[command]
+#[only_in(guilds)]
+#[aliases("q")]
+async fn queue(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    // pages are 1-indexed for humans
+    let page = if args.is_empty() {
+        1
+    } else {
+        match args.single::<usize>() {
+            Ok(page) if page > 0 => page,
+            _ => {
+                check_msg(
+                    msg.channel_id
+                        .say(&ctx.http, "The page has to be a number, starting from 1")
+                        .await,
+                );
+
+                return Ok(());
+            }
+        }
+    };
+
+    show_queue(ctx, &Invocation::from(msg), page)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn show_queue(ctx: &Context, inv: &Invocation, page: usize) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue().current_queue();
+
+        if queue.is_empty() {
+            return Ok(Reply::text(
+                ":x: the queue is empty, add something with `~play`",
+            ));
+        }
+
+        let pages = queue.len().div_ceil(QUEUE_PAGE_SIZE);
+        if page > pages {
+            return Ok(Reply::text(format!(
+                "There are only {} page(s) in the queue",
+                pages
+            )));
+        }
+
+        // the time until the current song ends, every song after it starts
+        // once everything before it has played out, this stays `None` as soon
+        // as one of the songs before has no known duration
+        let mut starts_in = match queue[0].get_info().await {
+            Ok(state) => queue[0]
+                .metadata()
+                .duration
+                .map(|d| d.checked_sub(state.position).unwrap_or_default()),
+            Err(_) => None,
+        };
+
+        let mut queue_str = String::new();
+        let mut total = Duration::from_secs(0);
+
+        for (n, track) in queue.iter().enumerate() {
+            let md = track.metadata();
+            let title = md.title.clone().unwrap_or("<no title>".into());
+            let length = md
+                .duration
+                .map(|d| hrtime::from_sec_padded(d.as_secs()))
+                .unwrap_or("??:??".into());
+            let requester = match track.typemap().read().await.get::<Requester>() {
+                Some(user) => user.mention().to_string(),
+                None => "someone".into(),
+            };
+            total += md.duration.unwrap_or_default();
+
+            let start = if n == 0 {
+                "playing now".to_string()
+            } else {
+                let start = match starts_in {
+                    Some(time) => format!("in {}", hrtime::from_sec_padded(time.as_secs())),
+                    None => "at some point".into(),
+                };
+                starts_in = starts_in.and_then(|time| md.duration.map(|d| time + d));
+                start
+            };
+
+            if n / QUEUE_PAGE_SIZE + 1 == page {
+                queue_str.push_str(&format!(
+                    "`[{n}]` **{title}** `[{length}]`\nrequested by {requester}, {start}\n"
+                ));
+            }
+        }
+
+        let looping = loop_mode(ctx, guild_id, &handler).await;
+
+        Ok(Reply::embed(None, |e| {
+            e.colour(embed_colour())
+                .title(format!("Queue ({} songs)", queue.len()))
+                .thumbnail(icon())
+                .description(queue_str)
+                .footer(|f| {
+                    f.text(format!(
+                        "Page {}/{} | Total duration: {} | Loop: {}",
+                        page,
+                        pages,
+                        hrtime::from_sec_padded(total.as_secs()),
+                        looping
+                    ))
+                    .icon_url(icon())
+                })
+        }))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+async fn skip(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    skip_song(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn skip_song(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+        skip_current(ctx, inv, &mut handler).await;
+
+        Ok(Reply::text(format!(
+            "Song skipped: {} in queue.",
+            handler.queue().len()
+        )))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+async fn skip_current(ctx: &Context, inv: &Invocation, handler: &mut Call) {
+    // skipped songs still come around again when the queue loops
+    if queue_looping(ctx, inv.guild_id).await {
+        if let Some(current) = handler.queue().current() {
+            requeue(ctx, inv.guild_id, inv.channel_id, handler, &current).await;
+        }
+    }
+
+    let _ = handler.queue().skip();
+
+    save_queue(ctx, inv.guild_id, handler, inv.channel_id).await;
+}
+
+/// Who voted to skip the current song of each guild.
+struct VoteSkips;
+
+impl TypeMapKey for VoteSkips {
+    type Value = Arc<Mutex<HashMap<GuildId, HashSet<UserId>>>>;
+}
+
+async fn vote_skips(ctx: &Context) -> Arc<Mutex<HashMap<GuildId, HashSet<UserId>>>> {
+    ctx.data
+        .read()
+        .await
+        .get::<VoteSkips>()
+        .cloned()
+        .expect("VoteSkips placed in at initialisation.")
+}
+
+#[command]
+#[only_in(guilds)]
+async fn voteskip(ctx: &Context, msg: &Message) -> CommandResult {
+    vote_skip(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+/// Votes to skip the current song, skipping it once enough of the people
+/// listening agree.
+async fn vote_skip(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let handler_lock = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => return Err(VoiceError::NotConnected.into()),
     };
+    let mut handler = handler_lock.lock().await;
 
-    if !url.starts_with("http") {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Must provide a valid URL")
-                .await,
-        );
+    let channel = match handler.current_channel() {
+        Some(channel) => ChannelId(channel.0),
+        None => return Err(VoiceError::NotConnected.into()),
+    };
+    let current = match handler.queue().current() {
+        Some(current) => current,
+        None => return Ok(Reply::text("Nothing is playing")),
+    };
 
-        return Ok(());
+    let listeners = idle::listeners(ctx, guild_id, channel).await;
+    if !listeners.contains(&inv.author) {
+        return Ok(Reply::text(format!(
+            "You have to be in <#{}> to vote",
+            channel.0
+        )));
     }
 
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+    let needed = (listeners.len() * config::get().voteskip_percent as usize)
+        .div_ceil(100)
+        .max(1);
+
+    let votes = vote_skips(ctx).await;
+    let mut votes = votes.lock().await;
+    let voters = votes.entry(guild_id).or_default();
+
+    let new_vote = voters.insert(inv.author);
+    // whoever left since doesn't count anymore
+    voters.retain(|voter| listeners.contains(voter));
+    let count = voters.len();
+
+    if count >= needed {
+        votes.remove(&guild_id);
+        drop(votes);
+
+        skip_current(ctx, inv, &mut handler).await;
+
+        Ok(Reply::text(format!(
+            "Vote passed ({}/{}), skipped `{}`",
+            count,
+            needed,
+            song_title(&current)
+        )))
+    } else if new_vote {
+        Ok(Reply::text(format!(
+            "Voted to skip `{}`: {}/{} votes",
+            song_title(&current),
+            count,
+            needed
+        )))
+    } else {
+        Ok(Reply::text(format!(
+            "You already voted: {}/{} votes",
+            count, needed
+        )))
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+async fn stop(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    stop_queue(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn stop_queue(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue();
+        let _ = queue.stop();
+
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::text("Queue cleared."))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases("rm")]
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (from, to) = match args.single::<String>().ok().and_then(|r| parse_range(&r)) {
+        Some(range) => range,
+        None => {
+            check_msg(
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        "Must provide a position like `3`, or a range like `3-7`",
+                    )
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    remove_songs(ctx, &Invocation::from(msg), from, to)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+#[command("move")]
+#[only_in(guilds)]
+#[aliases("mv")]
+async fn move_cmd(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (from, to) = match (args.single::<usize>(), args.single::<usize>()) {
+        (Ok(from), Ok(to)) => (from, to),
+        _ => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must provide the position to move from and to")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    move_song(ctx, &Invocation::from(msg), from, to)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn swap(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (a, b) = match (args.single::<usize>(), args.single::<usize>()) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must provide the two positions to swap")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    swap_songs(ctx, &Invocation::from(msg), a, b)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn skipto(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let to = match args.single::<usize>() {
+        Ok(to) => to,
+        Err(_) => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must provide the position to skip to")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    skip_to(ctx, &Invocation::from(msg), to)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+async fn shuffle(ctx: &Context, msg: &Message) -> CommandResult {
+    shuffle_queue(ctx, &Invocation::from(msg))
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+/// Shuffles everything but the song that's playing.
+async fn shuffle_queue(ctx: &Context, inv: &Invocation) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue();
+
+        if queue.len() < 3 {
+            return Ok(Reply::text("There's not enough queued up to shuffle"));
+        }
+
+        queue.modify_queue(|q| q.make_contiguous()[1..].shuffle(&mut rand::thread_rng()));
+
+        lazy::preload(&handler);
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::text(format!("Shuffled {} songs", queue.len() - 1)))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+/// Parses `n` or `a-b` into an inclusive range.
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    match text.split_once('-') {
+        Some((from, to)) => Some((from.trim().parse().ok()?, to.trim().parse().ok()?)),
+        None => text.parse().ok().map(|n| (n, n)),
+    }
+}
+
+fn song_title(track: &TrackHandle) -> String {
+    track
+        .metadata()
+        .title
+        .clone()
+        .unwrap_or("<no title>".into())
+}
+
+/// Checks `positions` are all upcoming songs of a queue `len` long, the one
+/// playing right now (`[0]`) can only be skipped.
+fn check_positions(len: usize, positions: &[usize]) -> Result<(), Reply> {
+    for &n in positions {
+        if n == 0 {
+            return Err(Reply::text(
+                "`[0]` is the song playing right now, use `skip` for that one",
+            ));
+        }
+        if n >= len {
+            return Err(Reply::text(format!(
+                "There's no song `[{}]`, the queue only goes up to `[{}]`",
+                n,
+                len.saturating_sub(1)
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// For when songs ended and left the queue while a command was looking at it.
+fn queue_changed() -> Reply {
+    Reply::text("The queue changed in the meantime, have another look and try again")
+}
+
+/// Removes the songs at positions `from` to `to`, both included.
+async fn remove_songs(ctx: &Context, inv: &Invocation, from: usize, to: usize) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    if from > to {
+        return Ok(Reply::text(
+            "The range has to go from the lower position to the higher one",
+        ));
+    }
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue();
+
+        if let Err(reply) = check_positions(queue.len(), &[from, to]) {
+            return Ok(reply);
+        }
+
+        // songs that finish leave the queue without waiting for our lock
+        let picked = match queue.current_queue().get(from..=to) {
+            Some(picked) => picked.to_vec(),
+            None => return Ok(queue_changed()),
+        };
+
+        // everyone gets to take back their own songs
+        if !perms::is_dj(ctx, guild_id, inv.author).await {
+            for track in &picked {
+                let requester = track.typemap().read().await.get::<Requester>().copied();
+                if requester != Some(inv.author) {
+                    return Ok(Reply::text(format!(
+                        "Only whoever queued `{}` or a DJ can remove it",
+                        song_title(track)
+                    )));
+                }
+            }
+        }
+
+        let removed = queue.modify_queue(|q| {
+            check_positions(q.len(), &[from, to])?;
+
+            // the checks above were for these exact songs
+            let same = q
+                .range(from..=to)
+                .map(|track| track.uuid())
+                .eq(picked.iter().map(TrackHandle::uuid));
+            if !same {
+                return Err(queue_changed());
+            }
+
+            Ok(q.drain(from..=to).collect::<Vec<_>>())
+        });
+        let removed = match removed {
+            Ok(removed) => removed,
+            Err(reply) => return Ok(reply),
+        };
+
+        let mut titles = String::new();
+        for track in removed {
+            // takes youtube-dl and ffmpeg down with it
+            let _ = track.stop();
+            titles.push_str(&format!("- {}\n", song_title(&track)));
+        }
+
+        lazy::preload(&handler);
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::text(format!("Removed from the queue:\n{}", titles)))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+async fn move_song(ctx: &Context, inv: &Invocation, from: usize, to: usize) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue();
+
+        // checked under the queue's own lock, finished songs leave it without ours
+        let moved = queue.modify_queue(|q| {
+            check_positions(q.len(), &[from, to])?;
+
+            let track = q.remove(from).ok_or_else(queue_changed)?;
+            let title = song_title(&track);
+            q.insert(to, track);
+            Ok(title)
+        });
+        let moved = match moved {
+            Ok(moved) => moved,
+            Err(reply) => return Ok(reply),
+        };
+
+        lazy::preload(&handler);
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::text(format!(
+            "Moved **{}** to position `[{}]`",
+            moved, to
+        )))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+async fn swap_songs(ctx: &Context, inv: &Invocation, a: usize, b: usize) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        let queue = handler.queue();
+
+        // checked under the queue's own lock, finished songs leave it without ours
+        let swapped = queue.modify_queue(|q| {
+            check_positions(q.len(), &[a, b])?;
+
+            q.swap(a, b);
+            Ok((song_title(&q[b]), song_title(&q[a])))
+        });
+        let (title_a, title_b) = match swapped {
+            Ok(titles) => titles,
+            Err(reply) => return Ok(reply),
+        };
+
+        lazy::preload(&handler);
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::text(format!(
+            "Swapped **{}** (now `[{}]`) and **{}** (now `[{}]`)",
+            title_a, b, title_b, a
+        )))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+/// Drops every song before position `to` and skips the current one, so that
+/// `to` plays next.
+async fn skip_to(ctx: &Context, inv: &Invocation, to: usize) -> Result<Reply> {
+    let guild_id = inv.guild_id;
 
     let manager = songbird::get(ctx)
         .await
@@ -881,179 +3062,449 @@ async fn play_fade(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
 
     if let Some(handler_lock) = manager.get(guild_id) {
         let mut handler = handler_lock.lock().await;
+        let queue = handler.queue();
+
+        // checked under the queue's own lock, finished songs leave it without ours
+        let skipped = queue.modify_queue(|q| {
+            check_positions(q.len(), &[to])?;
+
+            Ok(q.drain(1..to).collect::<Vec<_>>())
+        });
+        let skipped = match skipped {
+            Ok(skipped) => skipped,
+            Err(reply) => return Ok(reply),
+        };
+        for track in &skipped {
+            let _ = track.stop();
+        }
+
+        if queue_looping(ctx, guild_id).await {
+            let current = queue.current();
+            for track in current.iter().chain(skipped.iter().map(|q| &**q)) {
+                requeue(ctx, guild_id, inv.channel_id, &mut handler, track).await;
+            }
+        }
+        let queue = handler.queue();
+
+        let title = queue
+            .current_queue()
+            .get(1)
+            .map(song_title)
+            .unwrap_or("<no title>".into());
+
+        // songbird moves on to the next song once the current one ends
+        lazy::preload(&handler);
+        let _ = queue.skip();
+
+        save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+        Ok(Reply::text(format!(
+            "Skipped {} song(s), up next: **{}**",
+            skipped.len() + 1,
+            title
+        )))
+    } else {
+        Err(VoiceError::NotConnected.into())
+    }
+}
+
+#[command]
+#[only_in(guilds)]
+async fn restore(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let discard = args.single::<String>().is_ok_and(|arg| arg == "discard");
+
+    restore_queue(ctx, &Invocation::from(msg), discard)
+        .await?
+        .send(ctx, msg.channel_id)
+        .await;
+
+    Ok(())
+}
+
+async fn restore_queue(ctx: &Context, inv: &Invocation, discard: bool) -> Result<Reply> {
+    let guild_id = inv.guild_id;
+    let store = queue_store(ctx).await;
+
+    let snapshot = match store.load_pending(guild_id).await? {
+        Some(snapshot) => snapshot,
+        None => return Ok(Reply::text("There's no saved queue to restore")),
+    };
+
+    if discard {
+        if let Err(why) = store.remove_pending(guild_id).await {
+            tracing::error!("Couldn't remove saved queue: {:?}", why);
+        }
+        attachments::release(ctx, guild_id).await;
+        for track in &snapshot.tracks {
+            if track.source.resolver == AttachmentResolver::NAME {
+                attachments::forget(Path::new(&track.source.query));
+            }
+        }
+
+        return Ok(Reply::text("Forgot the saved queue"));
+    }
+
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let handler_lock = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock,
+        None => {
+            let guild = guild_id
+                .to_guild_cached(&ctx.cache)
+                .await
+                .ok_or(AoedeError::GuildUnavailable)?;
+
+            // back to where we were, or wherever the caller is
+            let connect_to = snapshot
+                .voice_channel
+                .or_else(|| {
+                    guild
+                        .voice_states
+                        .get(&inv.author)
+                        .and_then(|voice_state| voice_state.channel_id)
+                })
+                .ok_or(VoiceError::NoChannel)?;
+
+            connect(ctx, guild_id, connect_to, inv.channel_id).await?
+        }
+    };
+
+    let mut handler = handler_lock.lock().await;
+
+    // only pick the first song up where it stopped if it ends up playing right away
+    let mut resume = handler.queue().is_empty();
+    let total = snapshot.tracks.len();
+    let mut restored = 0;
+
+    for saved in snapshot.tracks {
+        let requested = Invocation {
+            author: saved.requester.unwrap_or(inv.author),
+            ..*inv
+        };
 
-        let source = match input::ytdl(&url).await {
-            Ok(source) => source,
+        let metadata = saved.metadata();
+        match queue_with_prebuf(saved.source, metadata, ctx, &requested, &mut handler).await {
+            Ok(_) => restored += 1,
             Err(why) => {
-                println!("Err starting source: {:?}", why);
+                why.report("restore").send(ctx, inv.channel_id).await;
+                continue;
+            }
+        }
 
-                check_msg(msg.channel_id.say(&ctx.http, "Error sourcing ffmpeg").await);
+        if resume {
+            resume = false;
 
-                return Ok(());
+            if let Some(current) = handler.queue().current() {
+                if !snapshot.position.is_zero() {
+                    let _ = current.seek_time(snapshot.position);
+                }
+                if snapshot.looping {
+                    let _ = current.enable_loop();
+                }
             }
-        };
+        }
+    }
 
-        // This handler object will allow you to, as needed,
-        // control the audio track via events and further commands.
-        let song = handler.play_source(source);
-        let send_http = ctx.http.clone();
-        let chan_id = msg.channel_id;
+    if snapshot.queue_looping {
+        set_queue_looping(ctx, guild_id, true).await;
+    }
 
-        // This shows how to periodically fire an event, in this case to
-        // periodically make a track quieter until it can be no longer heard.
-        let _ = song.add_event(
-            Event::Periodic(Duration::from_secs(5), Some(Duration::from_secs(7))),
-            SongFader {
-                chan_id,
-                http: send_http,
-            },
-        );
+    // it's part of the live queue now, restoring again would queue it twice
+    if let Err(why) = store.remove_pending(guild_id).await {
+        tracing::error!("Couldn't remove saved queue: {:?}", why);
+    }
+    attachments::release(ctx, guild_id).await;
+    save_queue(ctx, guild_id, &handler, inv.channel_id).await;
+
+    Ok(Reply::text(format!(
+        "Restored {}/{} songs, picking up at {}",
+        restored,
+        total,
+        hrtime::from_sec_padded(snapshot.position.as_secs())
+    )))
+}
 
-        let send_http = ctx.http.clone();
+/// Tells every guild which had a saved queue on startup that it can get it back.
+async fn offer_restores(ctx: &Context) {
+    let store = queue_store(ctx).await;
 
-        // This shows how to fire an event once an audio track completes,
-        // either due to hitting the end of the bytestream or stopped by user code.
-        let _ = song.add_event(
-            Event::Track(TrackEvent::End),
-            SongEndNotifier {
-                chan_id,
-                http: send_http,
-            },
-        );
+    let guilds = match store.pending_guilds().await {
+        Ok(guilds) => guilds,
+        Err(why) => {
+            tracing::error!("Couldn't list saved queues: {:?}", why);
+            return;
+        }
+    };
 
-        check_msg(msg.channel_id.say(&ctx.http, "Playing song").await);
-    } else {
+    for guild_id in guilds {
+        let snapshot = match store.load_pending(guild_id).await {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => continue,
+            Err(why) => {
+                tracing::error!("Couldn't load saved queue of {}: {:?}", guild_id, why);
+                continue;
+            }
+        };
+
+        let prefix = guild_settings(ctx, guild_id).await.prefix().to_string();
         check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
+            snapshot
+                .text_channel
+                .say(
+                    &ctx.http,
+                    format!(
+                        "I got restarted with {} song(s) still queued here, use `{}restore` to pick up where I left off or `{}restore discard` to forget them",
+                        snapshot.tracks.len(),
+                        prefix,
+                        prefix
+                    ),
+                )
                 .await,
         );
     }
-
-    Ok(())
 }
 
-struct SongFader {
-    chan_id: ChannelId,
-    http: Arc<Http>,
+/// Starts the next few songs whenever the queue moves along.
+struct QueuePreloader {
+    guild_id: GuildId,
+    context: Context,
 }
 
 #[async_trait]
-impl VoiceEventHandler for SongFader {
-    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
-        if let EventContext::Track(&[(state, track)]) = ctx {
-            let _ = track.set_volume(state.volume / 2.0);
+impl VoiceEventHandler for QueuePreloader {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        let manager = songbird::get(&self.context)
+            .await
+            .expect("Songbird Voice client placed in at initialisation")
+            .clone();
 
-            if state.volume < 1e-2 {
-                let _ = track.stop();
-                check_msg(self.chan_id.say(&self.http, "Stopping song...").await);
-                Some(Event::Cancel)
-            } else {
-                check_msg(self.chan_id.say(&self.http, "Volume reduced.").await);
-                None
-            }
-        } else {
-            None
+        if let Some(handler_lock) = manager.get(self.guild_id) {
+            let handler = handler_lock.lock().await;
+            lazy::preload(&handler);
         }
+
+        None
     }
 }
 
-struct SongEndNotifier {
+/// Snapshots the queue whenever a track ends, and every few seconds so the
+/// position of the current track stays fresh.
+struct QueueSaver {
+    guild_id: GuildId,
     chan_id: ChannelId,
-    http: Arc<Http>,
+    context: Context,
 }
 
 #[async_trait]
-impl VoiceEventHandler for SongEndNotifier {
+impl VoiceEventHandler for QueueSaver {
     async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
-        check_msg(
-            self.chan_id
-                .say(&self.http, "Song faded out completely!")
-                .await,
-        );
+        let manager = songbird::get(&self.context)
+            .await
+            .expect("Songbird Voice client placed in at initialisation")
+            .clone();
+
+        if let Some(handler_lock) = manager.get(self.guild_id) {
+            let handler = handler_lock.lock().await;
+            save_queue(&self.context, self.guild_id, &handler, self.chan_id).await;
+        }
 
         None
     }
 }
 
-/*
-#[command]
-#[only_in(guilds)]
-async fn queue(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+/// Writes the queue of `handler` to disk, or forgets it once it's empty.
+async fn save_queue(ctx: &Context, guild_id: GuildId, handler: &Call, chan_id: ChannelId) {
+    let store = queue_store(ctx).await;
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
+    let saved = match QueueSnapshot::of(handler, chan_id).await {
+        Some(mut snapshot) => {
+            snapshot.queue_looping = queue_looping(ctx, guild_id).await;
+            store.save(guild_id, &snapshot).await
+        }
+        None => store.remove(guild_id).await,
+    };
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
+    if let Err(why) = saved {
+        tracing::error!("Couldn't save queue: {:?}", why);
+    }
+}
 
-        // Here, we use lazy restartable sources to make sure that we don't pay
-        // for decoding, playback on tracks which aren't actually live yet.
-        let source = match Restartable::ytdl(url, true).await {
-            Ok(source) => source,
-            Err(why) => {
-                println!("Err starting source: {:?}", why);
+async fn queue_store(ctx: &Context) -> QueueStore {
+    ctx.data
+        .read()
+        .await
+        .get::<Queues>()
+        .cloned()
+        .expect("Queue store placed in at initialisation.")
+}
 
-                check_msg(msg.channel_id.say(&ctx.http, "Error sourcing ffmpeg").await);
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn prefix(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or(AoedeError::GuildUnavailable)?;
 
-                return Ok(());
-            }
-        };
+    let new_prefix = match args.single::<String>() {
+        Ok(prefix) => prefix,
+        Err(_) => {
+            let current = guild_settings(ctx, guild_id).await;
+            check_msg(
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        format!("The prefix here is `{}`", current.prefix()),
+                    )
+                    .await,
+            );
 
-        handler.enqueue_source(source.into());
+            return Ok(());
+        }
+    };
 
+    if new_prefix.chars().count() > 5 || new_prefix.contains('`') {
         check_msg(
             msg.channel_id
                 .say(
                     &ctx.http,
-                    format!("Added song to queue: position {}", handler.queue().len()),
+                    "The prefix can be at most 5 characters long and can't contain backticks",
                 )
                 .await,
         );
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
-                .await,
-        );
+
+        return Ok(());
     }
 
+    update_guild_settings(ctx, guild_id, |s| {
+        s.prefix = if new_prefix == config::get().prefix {
+            None
+        } else {
+            Some(new_prefix.clone())
+        };
+    })
+    .await
+    .map_err(AoedeError::Io)?;
+
+    check_msg(
+        msg.channel_id
+            .say(&ctx.http, format!("Prefix changed to `{}`", new_prefix))
+            .await,
+    );
+
     Ok(())
 }
-*/
 
 #[command]
 #[only_in(guilds)]
-async fn skip(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+#[required_permissions("MANAGE_GUILD")]
+async fn playlistmax(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or(AoedeError::GuildUnavailable)?;
+    let config_max = config::get().max_playlist_size;
+
+    let max = match args.single::<String>().as_deref() {
+        Ok("reset") => None,
+        Ok(n) => match n.parse::<usize>() {
+            Ok(n) if (1..=config_max).contains(&n) => Some(n),
+            _ => {
+                check_msg(
+                    msg.channel_id
+                        .say(
+                            &ctx.http,
+                            format!("Must be between 1 and {}, or `reset`", config_max),
+                        )
+                        .await,
+                );
 
-    let manager = songbird::get(ctx)
+                return Ok(());
+            }
+        },
+        Err(_) => {
+            let current = guild_settings(ctx, guild_id).await.max_playlist_size();
+            check_msg(
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        format!("Playlists import at most {} songs here", current),
+                    )
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    update_guild_settings(ctx, guild_id, |s| s.max_playlist_size = max)
         .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
+        .map_err(AoedeError::Io)?;
+
+    check_msg(
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "Playlists import at most {} songs now",
+                    max.unwrap_or(config_max)
+                ),
+            )
+            .await,
+    );
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue();
-        let _ = queue.skip();
+    Ok(())
+}
+
+#[command("247")]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+#[aliases("24/7")]
+async fn always_on(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or(AoedeError::GuildUnavailable)?;
+
+    let current = guild_settings(ctx, guild_id).await.always_on;
+    let always_on = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        Err(_) => !current,
+        Ok(_) => {
+            check_msg(
+                msg.channel_id
+                    .say(&ctx.http, "Must be `on`, `off`, or nothing to toggle")
+                    .await,
+            );
+
+            return Ok(());
+        }
+    };
+
+    update_guild_settings(ctx, guild_id, |s| s.always_on = always_on)
+        .await
+        .map_err(AoedeError::Io)?;
 
+    if always_on {
         check_msg(
             msg.channel_id
-                .say(
-                    &ctx.http,
-                    format!("Song skipped: {} in queue.", queue.len()),
-                )
+                .say(&ctx.http, "24/7 mode on, I'll stay until told to `leave`")
                 .await,
         );
     } else {
+        // pick up from wherever things are now
+        idle::check_listeners(ctx, guild_id).await;
+        if songbird::get(ctx)
+            .await
+            .expect("Songbird Voice client placed in at initialisation.")
+            .get(guild_id)
+            .is_some()
+        {
+            idle::schedule(ctx, guild_id, idle::Reason::Idle).await;
+        }
+
         check_msg(
             msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
+                .say(
+                    &ctx.http,
+                    "24/7 mode off, I'll leave when nobody's listening or nothing's playing",
+                )
                 .await,
         );
     }
@@ -1063,35 +3514,229 @@ async fn skip(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
 
 #[command]
 #[only_in(guilds)]
-async fn stop(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
+#[required_permissions("MANAGE_GUILD")]
+async fn dj(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.ok_or(AoedeError::GuildUnavailable)?;
+
+    let dj_role = match args.single::<String>().as_deref() {
+        Ok("off") => None,
+        Ok(role) => match role.parse::<RoleId>() {
+            Ok(role) => Some(role),
+            Err(_) => {
+                check_msg(
+                    msg.channel_id
+                        .say(&ctx.http, "Must be a role mention, a role id, or `off`")
+                        .await,
+                );
 
-    let manager = songbird::get(ctx)
-        .await
-        .expect("Songbird Voice client placed in at initialisation.")
-        .clone();
+                return Ok(());
+            }
+        },
+        Err(_) => {
+            let answer = match guild_settings(ctx, guild_id).await.dj_role {
+                Some(role) => format!("The DJ role here is {}", role.mention()),
+                None => "There's no DJ role here, everyone can control the music".into(),
+            };
+            check_msg(
+                msg.channel_id
+                    .send_message(&ctx.http, |m| {
+                        m.content(answer).allowed_mentions(|am| am.empty_parse())
+                    })
+                    .await,
+            );
 
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-        let queue = handler.queue();
-        let _ = queue.stop();
+            return Ok(());
+        }
+    };
 
-        check_msg(msg.channel_id.say(&ctx.http, "Queue cleared.").await);
-    } else {
-        check_msg(
-            msg.channel_id
-                .say(&ctx.http, "Not in a voice channel to play in")
-                .await,
-        );
-    }
+    update_guild_settings(ctx, guild_id, |s| s.dj_role = dj_role)
+        .await
+        .map_err(AoedeError::Io)?;
+
+    let answer = match dj_role {
+        Some(role) => format!(
+            "Only {} can skip other people's songs, stop, leave and rearrange the queue now",
+            role.mention()
+        ),
+        None => "DJ role removed, everyone can control the music".into(),
+    };
+    check_msg(
+        msg.channel_id
+            .send_message(&ctx.http, |m| {
+                m.content(answer).allowed_mentions(|am| am.empty_parse())
+            })
+            .await,
+    );
 
     Ok(())
 }
 
+/// Gets the stored settings of a guild, or the defaults if it has none.
+async fn guild_settings(ctx: &Context, guild_id: GuildId) -> GuildSettings {
+    let settings = ctx
+        .data
+        .read()
+        .await
+        .get::<Settings>()
+        .cloned()
+        .expect("Settings placed in at initialisation.");
+
+    let settings = settings.read().await;
+    settings.get(guild_id)
+}
+
+/// Changes the stored settings of a guild and saves them.
+async fn update_guild_settings<F>(ctx: &Context, guild_id: GuildId, f: F) -> io::Result<()>
+where
+    F: FnOnce(&mut GuildSettings),
+{
+    let settings = ctx
+        .data
+        .read()
+        .await
+        .get::<Settings>()
+        .cloned()
+        .expect("Settings placed in at initialisation.");
+
+    // written once the store is unlocked, nobody waits on the disk for theirs
+    let save = settings.write().await.update(guild_id, f)?;
+    save.write().await
+}
+
 /// Checks that a message successfully sent; if not, then logs why to stdout.
 fn check_msg(result: SerenityResult<Message>) {
     if let Err(why) = result {
-        println!("Error sending message: {:?}", why);
+        tracing::error!("Couldn't send message: {:?}", why);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1:30"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_timestamp("1:60"), None);
+        assert_eq!(parse_timestamp("1:60:00"), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("-1"), None);
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("99999999999999999:00:00"), None);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("3"), Some((3, 3)));
+        assert_eq!(parse_range("3-10"), Some((3, 10)));
+        assert_eq!(parse_range("3 - 10"), Some((3, 10)));
+        // the order is up to whoever uses it
+        assert_eq!(parse_range("10-3"), Some((10, 3)));
+        assert_eq!(parse_range("3-"), None);
+        assert_eq!(parse_range("a-b"), None);
+    }
+
+    #[test]
+    fn loop_modes() {
+        assert_eq!(LoopMode::parse("off"), Some(LoopMode::Off));
+        assert_eq!(LoopMode::parse("song"), Some(LoopMode::Track));
+        assert_eq!(LoopMode::parse("all"), Some(LoopMode::Queue));
+        assert_eq!(LoopMode::parse("0"), Some(LoopMode::Off));
+        assert_eq!(LoopMode::parse("3"), Some(LoopMode::Times(3)));
+        assert_eq!(LoopMode::parse("forever"), None);
+    }
+
+    #[test]
+    fn playlist_options_parse() {
+        let (url, options) =
+            PlaylistOptions::parse("https://a.b/list 3-10 --reverse --limit 4").unwrap();
+        assert_eq!(url, "https://a.b/list");
+        assert_eq!(options.range, Some((3, 10)));
+        assert!(options.reverse);
+        assert!(!options.shuffle);
+        assert_eq!(options.limit, Some(4));
+
+        let (_, options) = PlaylistOptions::parse("https://a.b/list 7").unwrap();
+        assert_eq!(options.range, Some((7, 7)));
+
+        // reversed and empty ranges
+        assert!(PlaylistOptions::parse("https://a.b/list 10-3").is_err());
+        assert!(PlaylistOptions::parse("https://a.b/list 0-3").is_err());
+
+        assert!(PlaylistOptions::parse("https://a.b/list --limit 0").is_err());
+        assert!(PlaylistOptions::parse("https://a.b/list --limit").is_err());
+    }
+
+    #[test]
+    fn playlist_options_apply() {
+        let playlist = || (1..=10).collect::<Vec<_>>();
+
+        let options = PlaylistOptions::default();
+        assert_eq!(options.apply(playlist(), 100), (playlist(), false));
+        assert_eq!(options.apply(playlist(), 3), (vec![1, 2, 3], true));
+
+        let options = PlaylistOptions {
+            range: Some((3, 5)),
+            reverse: true,
+            ..Default::default()
+        };
+        assert_eq!(options.apply(playlist(), 100), (vec![5, 4, 3], false));
+
+        // ends past the list stop at its end, however far past
+        let options = PlaylistOptions {
+            range: Some((8, usize::MAX)),
+            ..Default::default()
+        };
+        assert_eq!(options.apply(playlist(), 100), (vec![8, 9, 10], false));
+
+        let options = PlaylistOptions {
+            range: Some((11, 20)),
+            ..Default::default()
+        };
+        assert_eq!(options.apply(playlist(), 100), (vec![], false));
+
+        // a limit within the cap isn't cut off by it
+        let options = PlaylistOptions {
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(options.apply(playlist(), 5), (vec![1, 2], false));
+    }
+
+    #[test]
+    fn playlist_options_span() {
+        // one past the cap, to know it cut something off
+        let options = PlaylistOptions::default();
+        assert_eq!(options.span(100), (1, Some(101)));
+
+        let options = PlaylistOptions {
+            range: Some((3, 5)),
+            ..Default::default()
+        };
+        assert_eq!(options.span(100), (3, Some(5)));
+
+        let options = PlaylistOptions {
+            range: Some((10, 500)),
+            limit: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(options.span(100), (10, Some(29)));
+
+        // any of the range could end up picked
+        let options = PlaylistOptions {
+            shuffle: true,
+            limit: Some(20),
+            ..Default::default()
+        };
+        assert_eq!(options.span(100), (1, None));
+
+        let options = PlaylistOptions {
+            range: Some((10, usize::MAX)),
+            reverse: true,
+            ..Default::default()
+        };
+        assert_eq!(options.span(100), (10, Some(usize::MAX)));
     }
 }
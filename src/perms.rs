@@ -0,0 +1,135 @@
+//! Who gets to do what to the music, shared by the `~` command checks and
+//! the slash commands.
+use serenity::{
+    client::Context,
+    model::id::{ChannelId, GuildId, UserId},
+};
+
+use crate::{
+    error::{AoedeError, Result},
+    guild_settings, Invocation, Requester,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// The caller has to be listening along, if the bot is in a channel.
+    SameChannel,
+    /// Only DJs.
+    Dj,
+    /// Only whoever queued the current song, or DJs.
+    RequesterOrDj,
+}
+
+/// The rules a command has to pass, by its name.
+pub fn rules(command: &str) -> &'static [Rule] {
+    use Rule::*;
+
+    match command {
+        // joining again from elsewhere would move the bot away from its listeners
+        "join" | "play" | "search" | "local" | "play_playlist" | "play_fade" | "remove"
+        | "pause" | "resume" | "toggle" | "seek" | "forward" | "rewind" | "volume" | "loop"
+        | "songloop" | "abloop" | "voteskip" => &[SameChannel],
+        "skip" => &[SameChannel, RequesterOrDj],
+        "stop" | "leave" | "shuffle" | "move" | "swap" | "skipto" | "restore" => &[SameChannel, Dj],
+        _ => &[],
+    }
+}
+
+/// Checks every rule of `command`.
+pub async fn check(ctx: &Context, inv: &Invocation, command: &str) -> Result<()> {
+    for rule in rules(command) {
+        match rule {
+            Rule::SameChannel => same_channel(ctx, inv).await?,
+            Rule::Dj => {
+                if !is_dj(ctx, inv.guild_id, inv.author).await {
+                    return Err(AoedeError::Permission("Only DJs can do that".into()));
+                }
+            }
+            Rule::RequesterOrDj => {
+                // with nothing playing there's nobody to overrule
+                let requester = current_requester(ctx, inv.guild_id).await;
+                if requester.is_some_and(|requester| requester != inv.author)
+                    && !is_dj(ctx, inv.guild_id, inv.author).await
+                {
+                    let prefix = guild_settings(ctx, inv.guild_id).await.prefix().to_string();
+                    return Err(AoedeError::Permission(format!(
+                        "Only whoever queued this song or a DJ can do that, try `{}voteskip`",
+                        prefix
+                    )));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `user` counts as a DJ: everyone does until the guild picks a DJ
+/// role, after that its members and whoever can manage the server.
+pub async fn is_dj(ctx: &Context, guild_id: GuildId, user: UserId) -> bool {
+    let dj_role = match guild_settings(ctx, guild_id).await.dj_role {
+        Some(role) => role,
+        None => return true,
+    };
+
+    let member = match guild_id.member(ctx, user).await {
+        Ok(member) => member,
+        Err(_) => return false,
+    };
+
+    if member.roles.contains(&dj_role) {
+        return true;
+    }
+
+    match member.permissions(ctx).await {
+        Ok(permissions) => permissions.manage_guild(),
+        Err(_) => false,
+    }
+}
+
+async fn same_channel(ctx: &Context, inv: &Invocation) -> Result<()> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let bot_channel = match manager.get(inv.guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.current_channel(),
+        None => return Ok(()),
+    };
+    let bot_channel = match bot_channel {
+        Some(channel) => ChannelId(channel.0),
+        None => return Ok(()),
+    };
+
+    let author_channel = ctx
+        .cache
+        .guild_field(inv.guild_id, |guild| {
+            guild
+                .voice_states
+                .get(&inv.author)
+                .and_then(|voice_state| voice_state.channel_id)
+        })
+        .await
+        .flatten();
+
+    if author_channel == Some(bot_channel) {
+        Ok(())
+    } else {
+        Err(AoedeError::Permission(format!(
+            "You have to be in <#{}> for that",
+            bot_channel.0
+        )))
+    }
+}
+
+async fn current_requester(ctx: &Context, guild_id: GuildId) -> Option<UserId> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird Voice client placed in at initialisation.")
+        .clone();
+
+    let current = manager.get(guild_id)?.lock().await.queue().current()?;
+    let requester = current.typemap().read().await.get::<Requester>().copied();
+    requester
+}
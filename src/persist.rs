@@ -0,0 +1,311 @@
+//! Queue snapshots, so a restart or a crash doesn't throw away everyone's songs.
+//!
+//! Every guild with a non-empty queue gets one json file in the configured
+//! queue directory, rewritten whenever its queue changes. On startup those
+//! are set aside as pending, so the new queues can't overwrite them before
+//! anyone had the chance to `~restore` them. The same happens when the bot
+//! leaves a voice channel on its own.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::id::{ChannelId, GuildId, UserId},
+    prelude::TypeMapKey,
+};
+use songbird::{input::Metadata, tracks::LoopState, Call};
+
+use crate::{sources::Song, Requester, Source};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub voice_channel: Option<ChannelId>,
+    /// Where the commands were used, the restore offer goes there.
+    pub text_channel: ChannelId,
+    pub tracks: Vec<SavedTrack>,
+    /// How far into the first track we were.
+    pub position: Duration,
+    /// Whether the first track was looping.
+    pub looping: bool,
+    /// Whether the whole queue was looping.
+    #[serde(default)]
+    pub queue_looping: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedTrack {
+    pub source: Song,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub source_url: Option<String>,
+    pub duration: Option<Duration>,
+    pub requester: Option<UserId>,
+}
+
+impl SavedTrack {
+    /// What we still know about the track, enough to queue it without looking
+    /// it up again. `None` if it never had a title.
+    pub fn metadata(&self) -> Option<Metadata> {
+        let title = self.title.clone()?;
+
+        Some(Metadata {
+            title: Some(title),
+            duration: self.duration,
+            source_url: self.source_url.clone(),
+            channels: Some(2),
+            ..Default::default()
+        })
+    }
+}
+
+impl QueueSnapshot {
+    /// Takes a snapshot of everything still to be played in `handler`,
+    /// `None` if there's nothing worth saving.
+    pub async fn of(handler: &Call, text_channel: ChannelId) -> Option<Self> {
+        let mut tracks = Vec::new();
+        let mut position = Duration::default();
+        let mut looping = false;
+
+        for handle in handler.queue().current_queue() {
+            // tracks which just ended might not have been popped yet
+            let state = match handle.get_info().await {
+                Ok(state) if !state.playing.is_done() => state,
+                _ => continue,
+            };
+
+            let typemap = handle.typemap().read().await;
+            let source = match typemap.get::<Source>() {
+                Some(source) => source.clone(),
+                // not something we queued ourselves, nothing to restore it from
+                None => continue,
+            };
+
+            if tracks.is_empty() {
+                position = state.position;
+                looping = state.loops == LoopState::Infinite;
+            }
+
+            let md = handle.metadata();
+            tracks.push(SavedTrack {
+                source,
+                title: md.title.clone(),
+                source_url: md.source_url.clone(),
+                duration: md.duration,
+                requester: typemap.get::<Requester>().copied(),
+            });
+        }
+
+        if tracks.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            voice_channel: handler.current_channel().map(|c| ChannelId(c.0)),
+            text_channel,
+            tracks,
+            position,
+            looping,
+            queue_looping: false,
+        })
+    }
+}
+
+const LIVE: &str = ".json";
+const PENDING: &str = ".pending.json";
+
+/// The directory holding every guild's snapshot.
+#[derive(Clone, Debug)]
+pub struct QueueStore {
+    dir: PathBuf,
+}
+
+impl QueueStore {
+    /// Opens the queue directory, and sets aside the snapshots saved before
+    /// this start as pending. A pending snapshot that was never restored is
+    /// replaced by the newer one.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let store = Self { dir };
+        for guild_id in store.guilds(LIVE)? {
+            fs::rename(store.path(guild_id, LIVE), store.path(guild_id, PENDING))?;
+        }
+
+        Ok(store)
+    }
+
+    fn path(&self, guild_id: GuildId, kind: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", guild_id.0, kind))
+    }
+
+    /// Saves the live queue of `guild_id`.
+    pub async fn save(&self, guild_id: GuildId, snapshot: &QueueSnapshot) -> io::Result<()> {
+        let json = serde_json::to_string(snapshot)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // same dance as the settings, never leave a half written file behind
+        let path = self.path(guild_id, LIVE);
+        blocking(move || {
+            let tmp = path.with_extension("json.tmp");
+            fs::write(&tmp, json)?;
+            fs::rename(&tmp, &path)
+        })
+        .await
+    }
+
+    /// Forgets the live queue of `guild_id`, the pending one stays.
+    pub async fn remove(&self, guild_id: GuildId) -> io::Result<()> {
+        let path = self.path(guild_id, LIVE);
+        blocking(move || remove_file(&path)).await
+    }
+
+    /// Sets the live queue of `guild_id` aside as pending, for when the bot
+    /// left on its own and `~restore` should still bring it back.
+    pub async fn set_aside(&self, guild_id: GuildId) -> io::Result<()> {
+        let (live, pending) = (self.path(guild_id, LIVE), self.path(guild_id, PENDING));
+        blocking(move || match fs::rename(&live, &pending) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            renamed => renamed,
+        })
+        .await
+    }
+
+    /// The queue `guild_id` had before the restart or before the bot left on
+    /// its own, if it's still waiting to be restored.
+    pub async fn load_pending(&self, guild_id: GuildId) -> io::Result<Option<QueueSnapshot>> {
+        let path = self.path(guild_id, PENDING);
+        let json = match blocking(move || fs::read_to_string(path)).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Forgets the queue `guild_id` had waiting to be restored.
+    pub async fn remove_pending(&self, guild_id: GuildId) -> io::Result<()> {
+        let path = self.path(guild_id, PENDING);
+        blocking(move || remove_file(&path)).await
+    }
+
+    /// Every guild that has a snapshot from before the restart waiting.
+    pub async fn pending_guilds(&self) -> io::Result<Vec<GuildId>> {
+        let store = self.clone();
+        blocking(move || store.guilds(PENDING)).await
+    }
+
+    /// Every guild that has a snapshot of `kind` lying around.
+    fn guilds(&self, kind: &str) -> io::Result<Vec<GuildId>> {
+        let mut guilds = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(kind))
+                .and_then(|id| id.parse().ok());
+
+            if let Some(id) = id {
+                guilds.push(GuildId(id));
+            }
+        }
+
+        Ok(guilds)
+    }
+}
+
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Runs file system work off the async runtime.
+async fn blocking<T, F>(f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+}
+
+pub struct Queues;
+
+impl TypeMapKey for Queues {
+    type Value = QueueStore;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(query: &str) -> QueueSnapshot {
+        QueueSnapshot {
+            voice_channel: None,
+            text_channel: ChannelId(1),
+            tracks: vec![SavedTrack {
+                source: Song::new("ytdl", query),
+                title: Some(query.into()),
+                source_url: None,
+                duration: None,
+                requester: None,
+            }],
+            position: Duration::ZERO,
+            looping: false,
+            queue_looping: false,
+        }
+    }
+
+    fn first_query(snapshot: Option<QueueSnapshot>) -> String {
+        snapshot.unwrap().tracks.remove(0).source.query
+    }
+
+    #[tokio::test]
+    async fn snapshots_wait_until_restored() {
+        let dir = std::env::temp_dir().join(format!("aoede-queues-{}", std::process::id()));
+        let guild_id = GuildId(7);
+
+        // live saves aren't up for restoring
+        let store = QueueStore::new(&dir).unwrap();
+        store.save(guild_id, &snapshot("before")).await.unwrap();
+        assert!(store.load_pending(guild_id).await.unwrap().is_none());
+
+        // until the next start
+        let store = QueueStore::new(&dir).unwrap();
+        assert_eq!(store.pending_guilds().await.unwrap(), vec![guild_id]);
+        store.save(guild_id, &snapshot("after")).await.unwrap();
+        assert_eq!(
+            first_query(store.load_pending(guild_id).await.unwrap()),
+            "before"
+        );
+
+        // or until the bot leaves on its own
+        store.set_aside(guild_id).await.unwrap();
+        assert_eq!(
+            first_query(store.load_pending(guild_id).await.unwrap()),
+            "after"
+        );
+        store.set_aside(guild_id).await.unwrap();
+        assert_eq!(
+            first_query(store.load_pending(guild_id).await.unwrap()),
+            "after"
+        );
+
+        store.remove_pending(guild_id).await.unwrap();
+        assert!(store.load_pending(guild_id).await.unwrap().is_none());
+        assert!(store.pending_guilds().await.unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
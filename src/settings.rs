@@ -0,0 +1,171 @@
+//! Per-guild settings, kept in a json file so they survive restarts.
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::id::{GuildId, RoleId},
+    prelude::{Mutex, RwLock, TypeMapKey},
+};
+
+use crate::config;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Command prefix, `None` means the one from the config.
+    pub prefix: Option<String>,
+    /// Volume new songs start at in percent, `None` means the one from the config.
+    pub volume: Option<u16>,
+    /// 24/7 mode, stays in voice even with nobody listening or nothing playing.
+    pub always_on: bool,
+    /// Role allowed to skip other people's songs, stop and the like, `None`
+    /// means everyone is a DJ.
+    pub dj_role: Option<RoleId>,
+    /// Most songs one playlist import adds, `None` means the config's maximum.
+    pub max_playlist_size: Option<usize>,
+}
+
+impl GuildSettings {
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or(&config::get().prefix)
+    }
+
+    pub fn volume(&self) -> u16 {
+        self.volume.unwrap_or(config::get().default_volume)
+    }
+
+    pub fn max_playlist_size(&self) -> usize {
+        let max = config::get().max_playlist_size;
+        self.max_playlist_size.map_or(max, |size| size.min(max))
+    }
+}
+
+/// All the guild settings, written back to disk on every change.
+#[derive(Debug)]
+pub struct SettingsStore {
+    path: PathBuf,
+    guilds: HashMap<u64, GuildSettings>,
+    /// Counts the changes, so that saves can tell which one is the newest.
+    changes: u64,
+    /// The change last written to disk, held while writing so that saves
+    /// don't race each other for the file.
+    written: Arc<Mutex<u64>>,
+}
+
+impl SettingsStore {
+    /// Loads the store from `path`, a missing file is just an empty store.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let guilds = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            guilds,
+            changes: 0,
+            written: Arc::new(Mutex::new(0)),
+        })
+    }
+
+    /// Settings of a guild, or the defaults if it never changed anything.
+    pub fn get(&self, guild_id: GuildId) -> GuildSettings {
+        self.guilds.get(&guild_id.0).cloned().unwrap_or_default()
+    }
+
+    /// Changes the settings of a guild. The change only gets to disk once
+    /// the returned save is written, after letting go of the store.
+    pub fn update<F>(&mut self, guild_id: GuildId, f: F) -> io::Result<SettingsSave>
+    where
+        F: FnOnce(&mut GuildSettings),
+    {
+        f(self.guilds.entry(guild_id.0).or_default());
+        self.changes += 1;
+
+        let json = serde_json::to_string_pretty(&self.guilds)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(SettingsSave {
+            path: self.path.clone(),
+            json,
+            change: self.changes,
+            written: self.written.clone(),
+        })
+    }
+}
+
+/// The whole store as it was after a change, still to be written to disk.
+#[must_use = "the change isn't saved until it's written"]
+pub struct SettingsSave {
+    path: PathBuf,
+    json: String,
+    change: u64,
+    written: Arc<Mutex<u64>>,
+}
+
+impl SettingsSave {
+    /// Writes the store, unless a later change got written first.
+    pub async fn write(self) -> io::Result<()> {
+        let mut written = self.written.lock().await;
+        if *written > self.change {
+            return Ok(());
+        }
+
+        let (path, json) = (self.path, self.json);
+        tokio::task::spawn_blocking(move || {
+            // write next to the real file and swap it in, so a crash mid-write
+            // doesn't eat everyone's settings
+            let tmp = path.with_extension("json.tmp");
+            fs::write(&tmp, json)?;
+            fs::rename(&tmp, &path)
+        })
+        .await
+        .map_err(io::Error::other)??;
+
+        *written = self.change;
+
+        Ok(())
+    }
+}
+
+pub struct Settings;
+
+impl TypeMapKey for Settings {
+    type Value = Arc<RwLock<SettingsStore>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn saves_survive_a_reload() {
+        let path = std::env::temp_dir().join(format!("aoede-settings-{}.json", std::process::id()));
+        let guild_id = GuildId(7);
+
+        let mut store = SettingsStore::load(&path).unwrap();
+        let first = store.update(guild_id, |s| s.always_on = true).unwrap();
+        let second = store
+            .update(guild_id, |s| s.prefix = Some("!".into()))
+            .unwrap();
+
+        // the older change doesn't overwrite the newer one when it's late
+        second.write().await.unwrap();
+        first.write().await.unwrap();
+
+        let reloaded = SettingsStore::load(&path).unwrap().get(guild_id);
+        assert!(reloaded.always_on);
+        assert_eq!(reloaded.prefix.as_deref(), Some("!"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
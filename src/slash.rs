@@ -0,0 +1,478 @@
+//! Slash command front-end, these just parse the interaction options and run
+//! the same code the `~` commands do.
+use std::time::Duration;
+
+use serde_json::Value;
+use serenity::{
+    client::Context,
+    model::interactions::{
+        application_command::{
+            ApplicationCommand, ApplicationCommandInteraction, ApplicationCommandOptionType,
+        },
+        InteractionResponseType,
+    },
+    Result as SerenityResult,
+};
+
+use crate::{
+    change_volume, config, enqueue_local, enqueue_playlist, enqueue_song, error::Result,
+    join_voice, leave_voice, move_song, now_playing, parse_timestamp, perms, pick_search_result,
+    play_fading, pong, remove_songs, restore_queue, seek_song, set_ab_loop, set_loop_mode,
+    set_paused, show_queue, shuffle_queue, skip_song, skip_to, stop_queue, swap_songs,
+    toggle_song_loop, vote_skip, Invocation, LoopMode, PlaylistOptions, Reply, Seek,
+};
+
+/// Registers every slash command globally, replacing whatever was there before.
+pub async fn register(ctx: &Context) -> SerenityResult<Vec<ApplicationCommand>> {
+    ApplicationCommand::set_global_application_commands(&ctx.http, |commands| {
+        commands
+            .create_application_command(|c| c.name("join").description("Join your voice channel"))
+            .create_application_command(|c| c.name("leave").description("Leave the voice channel"))
+            .create_application_command(|c| {
+                c.name("play")
+                    .description("Queue a song from a URL or a youtube search")
+                    .create_option(|o| {
+                        o.name("query")
+                            .description("URL or search query")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("search")
+                    .description("Search youtube and pick which result to queue")
+                    .create_option(|o| {
+                        o.name("query")
+                            .description("Search query")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("local")
+                    .description("Play a file from the music library")
+                    .create_option(|o| {
+                        o.name("query")
+                            .description("Path or part of the file name")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("play_playlist")
+                    .description("Queue every song of a playlist")
+                    .create_option(|o| {
+                        o.name("url")
+                            .description("URL of the playlist")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+                    .create_option(|o| {
+                        o.name("range")
+                            .description("Only these entries, like 3-10")
+                            .kind(ApplicationCommandOptionType::String)
+                    })
+                    .create_option(|o| {
+                        o.name("reverse")
+                            .description("Queue the songs last to first")
+                            .kind(ApplicationCommandOptionType::Boolean)
+                    })
+                    .create_option(|o| {
+                        o.name("shuffle")
+                            .description("Queue the songs in a random order")
+                            .kind(ApplicationCommandOptionType::Boolean)
+                    })
+                    .create_option(|o| {
+                        o.name("limit")
+                            .description("Queue at most this many songs")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("play_fade")
+                    .description("Play a song that slowly fades out")
+                    .create_option(|o| {
+                        o.name("url")
+                            .description("URL to a video or audio")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("queue")
+                    .description("Show what's coming up")
+                    .create_option(|o| {
+                        o.name("page")
+                            .description("Page of the queue, starting from 1")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                    })
+            })
+            .create_application_command(|c| c.name("skip").description("Skip the current song"))
+            .create_application_command(|c| {
+                c.name("voteskip")
+                    .description("Vote to skip the current song")
+            })
+            .create_application_command(|c| {
+                c.name("stop")
+                    .description("Stop playing and clear the queue")
+            })
+            .create_application_command(|c| {
+                c.name("nowplaying")
+                    .description("Show the song that's playing")
+            })
+            .create_application_command(|c| {
+                c.name("songloop")
+                    .description("Toggle looping the current song forever")
+            })
+            .create_application_command(|c| {
+                c.name("volume")
+                    .description("Show or change the volume")
+                    .create_option(|o| {
+                        o.name("percent")
+                            .description("New volume in percent")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(0)
+                            .max_int_value(config::MAX_VOLUME as i32)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("remove")
+                    .description("Remove songs from the queue")
+                    .create_option(|o| {
+                        o.name("from")
+                            .description("Position of the (first) song to remove")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+                    .create_option(|o| {
+                        o.name("to")
+                            .description("Last position to remove, for a range")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("move")
+                    .description("Move a song to another position in the queue")
+                    .create_option(|o| {
+                        o.name("from")
+                            .description("Position of the song to move")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+                    .create_option(|o| {
+                        o.name("to")
+                            .description("Position to move it to")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("swap")
+                    .description("Swap two songs in the queue")
+                    .create_option(|o| {
+                        o.name("a")
+                            .description("Position of one song")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+                    .create_option(|o| {
+                        o.name("b")
+                            .description("Position of the other song")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("skipto")
+                    .description("Skip straight to a song in the queue")
+                    .create_option(|o| {
+                        o.name("position")
+                            .description("Position of the song to play next")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("shuffle")
+                    .description("Shuffle the songs after the current one")
+            })
+            .create_application_command(|c| c.name("pause").description("Pause the song"))
+            .create_application_command(|c| c.name("resume").description("Resume the song"))
+            .create_application_command(|c| {
+                c.name("toggle").description("Pause or resume the song")
+            })
+            .create_application_command(|c| {
+                c.name("seek")
+                    .description("Jump to a point in the current song")
+                    .create_option(|o| {
+                        o.name("time")
+                            .description("Like 1:30 or 1:02:03")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("forward")
+                    .description("Skip ahead in the current song")
+                    .create_option(|o| {
+                        o.name("seconds")
+                            .description("How many seconds to skip")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("rewind")
+                    .description("Go back in the current song")
+                    .create_option(|o| {
+                        o.name("seconds")
+                            .description("How many seconds to go back")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .min_int_value(1)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("loop")
+                    .description("Loop the current song, the whole queue, or nothing")
+                    .create_option(|o| {
+                        o.name("mode")
+                            .description(
+                                "queue, track, off, or how many more times to play the song",
+                            )
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("abloop")
+                    .description("Loop a section of the current song, leave out both times to stop")
+                    .create_option(|o| {
+                        o.name("start")
+                            .description("Where the section starts, like 1:30")
+                            .kind(ApplicationCommandOptionType::String)
+                    })
+                    .create_option(|o| {
+                        o.name("end")
+                            .description("Where the section ends, like 2:05")
+                            .kind(ApplicationCommandOptionType::String)
+                    })
+            })
+            .create_application_command(|c| {
+                c.name("restore")
+                    .description("Bring back the queue from before a restart")
+                    .create_option(|o| {
+                        o.name("discard")
+                            .description("Forget the saved queue instead")
+                            .kind(ApplicationCommandOptionType::Boolean)
+                    })
+            })
+            .create_application_command(|c| c.name("ping").description("Pong!"))
+    })
+    .await
+}
+
+/// Runs a slash command and answers the interaction with its reply.
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) {
+    let guild_id = match command.guild_id {
+        Some(guild_id) => guild_id,
+        None => {
+            let response = command
+                .create_interaction_response(&ctx.http, |r| {
+                    r.kind(InteractionResponseType::ChannelMessageWithSource)
+                        .interaction_response_data(|d| {
+                            d.content("Music commands only work in servers")
+                        })
+                })
+                .await;
+
+            if let Err(why) = response {
+                tracing::error!("Couldn't respond to interaction: {:?}", why);
+            }
+
+            return;
+        }
+    };
+
+    let inv = Invocation {
+        guild_id,
+        channel_id: command.channel_id,
+        author: command.user.id,
+    };
+
+    // sourcing songs easily takes longer than the 3 seconds discord gives us
+    if let Err(why) = command.defer(&ctx.http).await {
+        tracing::error!("Couldn't defer interaction: {:?}", why);
+
+        return;
+    }
+
+    let reply = match dispatch(ctx, command, &inv).await {
+        Ok(reply) => reply,
+        Err(why) => why.report(&command.data.name),
+    };
+
+    let response = command
+        .edit_original_interaction_response(&ctx.http, |r| match reply {
+            Reply::Text(text) => r.content(text),
+            Reply::Embed { content, embed } => {
+                if let Some(content) = content {
+                    r.content(content);
+                }
+                r.add_embed(embed)
+            }
+        })
+        .await;
+
+    if let Err(why) = response {
+        tracing::error!("Couldn't respond to interaction: {:?}", why);
+    }
+}
+
+/// Runs the command that was picked, the same way its `~` command would.
+async fn dispatch(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    inv: &Invocation,
+) -> Result<Reply> {
+    // slash commands skip the framework, and with it its checks
+    perms::check(ctx, inv, &command.data.name).await?;
+
+    match command.data.name.as_str() {
+        "join" => join_voice(ctx, inv).await,
+        "leave" => leave_voice(ctx, inv).await,
+        "play" => enqueue_song(ctx, inv, string_option(command, "query")).await,
+        "search" => pick_search_result(ctx, inv, string_option(command, "query")).await,
+        "local" => enqueue_local(ctx, inv, string_option(command, "query")).await,
+        "play_playlist" => match playlist_options(command) {
+            Ok(options) => {
+                enqueue_playlist(ctx, inv, string_option(command, "url"), options).await
+            }
+            Err(why) => Ok(Reply::text(why)),
+        },
+        "play_fade" => play_fading(ctx, inv, string_option(command, "url")).await,
+        "queue" => {
+            let page = int_option(command, "page").unwrap_or(1).max(1) as usize;
+            show_queue(ctx, inv, page).await
+        }
+        "skip" => skip_song(ctx, inv).await,
+        "voteskip" => vote_skip(ctx, inv).await,
+        "stop" => stop_queue(ctx, inv).await,
+        "nowplaying" => now_playing(ctx, inv).await,
+        "songloop" => toggle_song_loop(ctx, inv).await,
+        "volume" => {
+            let volume = int_option(command, "percent").map(|v| v.clamp(0, u16::MAX as i64) as u16);
+            change_volume(ctx, inv, volume).await
+        }
+        "remove" => {
+            let from = position_option(command, "from");
+            let to = int_option(command, "to").map_or(from, |to| to.max(0) as usize);
+            remove_songs(ctx, inv, from, to).await
+        }
+        "move" => {
+            let (from, to) = (
+                position_option(command, "from"),
+                position_option(command, "to"),
+            );
+            move_song(ctx, inv, from, to).await
+        }
+        "swap" => {
+            let (a, b) = (position_option(command, "a"), position_option(command, "b"));
+            swap_songs(ctx, inv, a, b).await
+        }
+        "skipto" => skip_to(ctx, inv, position_option(command, "position")).await,
+        "shuffle" => shuffle_queue(ctx, inv).await,
+        "pause" => set_paused(ctx, inv, Some(true)).await,
+        "resume" => set_paused(ctx, inv, Some(false)).await,
+        "toggle" => set_paused(ctx, inv, None).await,
+        "seek" => match parse_timestamp(&string_option(command, "time")) {
+            Some(position) => seek_song(ctx, inv, Seek::To(position)).await,
+            None => Ok(Reply::text("Must provide a time like `1:30` or `1:02:03`")),
+        },
+        "forward" => {
+            let secs = int_option(command, "seconds").unwrap_or(0).max(0) as u64;
+            seek_song(ctx, inv, Seek::Forward(Duration::from_secs(secs))).await
+        }
+        "rewind" => {
+            let secs = int_option(command, "seconds").unwrap_or(0).max(0) as u64;
+            seek_song(ctx, inv, Seek::Back(Duration::from_secs(secs))).await
+        }
+        "loop" => match LoopMode::parse(&string_option(command, "mode")) {
+            Some(mode) => set_loop_mode(ctx, inv, mode).await,
+            None => Ok(Reply::text(
+                "Loop mode has to be `queue`, `track`, `off` or how many more times to play the song",
+            )),
+        },
+        "abloop" => {
+            let (start, end) = (string_option(command, "start"), string_option(command, "end"));
+            if start.is_empty() && end.is_empty() {
+                set_ab_loop(ctx, inv, None).await
+            } else {
+                match (parse_timestamp(&start), parse_timestamp(&end)) {
+                    (Some(start), Some(end)) => set_ab_loop(ctx, inv, Some((start, end))).await,
+                    _ => Ok(Reply::text("Times have to look like `1:30` or `1:02:03`")),
+                }
+            }
+        }
+        "restore" => {
+            let discard = bool_option(command, "discard").unwrap_or(false);
+            restore_queue(ctx, inv, discard).await
+        }
+        "ping" => Ok(pong()),
+        _ => Ok(Reply::text("I don't know that command, try again in a bit")),
+    }
+}
+
+fn option<'a>(command: &'a ApplicationCommandInteraction, name: &str) -> Option<&'a Value> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|o| o.name == name)
+        .and_then(|o| o.value.as_ref())
+}
+
+/// A string option, missing ones are empty like an empty `~` argument list.
+fn string_option(command: &ApplicationCommandInteraction, name: &str) -> String {
+    option(command, name)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn int_option(command: &ApplicationCommandInteraction, name: &str) -> Option<i64> {
+    option(command, name).and_then(Value::as_i64)
+}
+
+fn bool_option(command: &ApplicationCommandInteraction, name: &str) -> Option<bool> {
+    option(command, name).and_then(Value::as_bool)
+}
+
+fn playlist_options(command: &ApplicationCommandInteraction) -> Result<PlaylistOptions, String> {
+    let range = match option(command, "range").and_then(Value::as_str) {
+        Some(range) => Some(PlaylistOptions::parse_range(range)?),
+        None => None,
+    };
+
+    Ok(PlaylistOptions {
+        range,
+        reverse: bool_option(command, "reverse").unwrap_or(false),
+        shuffle: bool_option(command, "shuffle").unwrap_or(false),
+        limit: int_option(command, "limit").map(|limit| limit.max(1) as usize),
+    })
+}
+
+/// A queue position, missing ones are `0` which is never valid.
+fn position_option(command: &ApplicationCommandInteraction, name: &str) -> usize {
+    int_option(command, name).unwrap_or(0).max(0) as usize
+}
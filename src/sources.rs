@@ -0,0 +1,312 @@
+//! Where songs come from. Each [`SourceResolver`] knows one kind of source,
+//! `~play` asks them in order which one handles its query.
+use std::{path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serenity::{async_trait, client::Context, model::id::GuildId, prelude::TypeMapKey};
+use songbird::{
+    input::{Input, Metadata},
+    tracks::TrackHandle,
+};
+
+use crate::{attachments::AttachmentResolver, error::Result, lazy, library::LocalResolver};
+
+/// What a track was sourced from, enough to source it again after a restart
+/// or when the queue loops back to it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Song {
+    /// Name of the resolver that sourced it.
+    pub resolver: String,
+    /// Whatever that resolver needs to find it, a URL, a search, a path...
+    pub query: String,
+}
+
+impl Song {
+    pub fn new(resolver: &str, query: impl Into<String>) -> Self {
+        Self {
+            resolver: resolver.into(),
+            query: query.into(),
+        }
+    }
+}
+
+/// A sourced song, ready to be made into a track.
+pub struct Resolved {
+    pub input: Input,
+    /// What the track shows, replaces whatever `input` came with.
+    pub metadata: Metadata,
+    pub buffered: lazy::Buffered,
+    /// What to source it from next time, searches pin down the video they found.
+    pub song: Song,
+}
+
+#[async_trait]
+pub trait SourceResolver: Send + Sync {
+    /// Unique name, saved with every song this resolver sourced.
+    fn name(&self) -> &'static str;
+
+    /// Whether a `~play` query is for this resolver. Ones that are only
+    /// ever picked by name, by their own command, handle nothing.
+    fn handles(&self, query: &str) -> bool;
+
+    /// Sources `query`, without starting to stream it yet. `known` metadata
+    /// is from an earlier time it was sourced, if there was one.
+    async fn resolve(&self, query: &str, known: Option<Metadata>) -> Result<Resolved>;
+
+    /// Called with every track made from this resolver's songs, before it's
+    /// queued, for anything it has to hook into the track.
+    async fn track_added(
+        &self,
+        _ctx: &Context,
+        _guild_id: GuildId,
+        _track: &TrackHandle,
+        _query: &str,
+    ) {
+    }
+}
+
+/// Every resolver, asked in order.
+pub struct Resolvers(Vec<Box<dyn SourceResolver>>);
+
+impl Resolvers {
+    pub fn new(resolvers: Vec<Box<dyn SourceResolver>>) -> Self {
+        Self(resolvers)
+    }
+
+    /// The first resolver that handles `query`.
+    pub fn for_query(&self, query: &str) -> Option<&dyn SourceResolver> {
+        self.0
+            .iter()
+            .find(|resolver| resolver.handles(query))
+            .map(|resolver| resolver.as_ref())
+    }
+
+    /// The resolver called `name`.
+    pub fn get(&self, name: &str) -> Option<&dyn SourceResolver> {
+        self.0
+            .iter()
+            .find(|resolver| resolver.name() == name)
+            .map(|resolver| resolver.as_ref())
+    }
+}
+
+impl Default for Resolvers {
+    fn default() -> Self {
+        Self::new(vec![
+            Box::new(DirectHttp),
+            Box::new(Ytdl),
+            Box::new(LocalResolver),
+            Box::new(AttachmentResolver),
+            // takes anything, has to come last
+            Box::new(Search),
+        ])
+    }
+}
+
+impl TypeMapKey for Resolvers {
+    type Value = Arc<Resolvers>;
+}
+
+/// URLs only go up to the first space, whatever comes after is ignored.
+fn url_of(query: &str) -> &str {
+    query.split_whitespace().next().unwrap_or_default()
+}
+
+/// Anything youtube-dl can play from a URL.
+pub struct Ytdl;
+
+impl Ytdl {
+    pub const NAME: &'static str = "ytdl";
+}
+
+#[async_trait]
+impl SourceResolver for Ytdl {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn handles(&self, query: &str) -> bool {
+        query.starts_with("http")
+    }
+
+    async fn resolve(&self, query: &str, known: Option<Metadata>) -> Result<Resolved> {
+        let url = url_of(query);
+        let (source, buffered) = lazy::ytdl(url.into(), known).await?;
+
+        let input: Input = source.into();
+        Ok(Resolved {
+            metadata: *input.metadata.clone(),
+            input,
+            buffered,
+            song: Song::new(Self::NAME, url),
+        })
+    }
+}
+
+/// The first youtube search result.
+pub struct Search;
+
+impl Search {
+    pub const NAME: &'static str = "search";
+}
+
+#[async_trait]
+impl SourceResolver for Search {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn handles(&self, query: &str) -> bool {
+        !query.is_empty()
+    }
+
+    async fn resolve(&self, query: &str, known: Option<Metadata>) -> Result<Resolved> {
+        let (source, buffered) = lazy::ytdl(format!("ytsearch1:{}", query), known).await?;
+
+        let input: Input = source.into();
+        let metadata = *input.metadata.clone();
+
+        // restore the exact video it found, not whatever it finds next time
+        let song = match &metadata.source_url {
+            Some(url) => Song::new(Ytdl::NAME, url.clone()),
+            None => Song::new(Self::NAME, query),
+        };
+
+        Ok(Resolved {
+            input,
+            metadata,
+            buffered,
+            song,
+        })
+    }
+}
+
+/// Audio files served straight over http, internet radio streams included,
+/// played by ffmpeg without youtube-dl in between.
+pub struct DirectHttp;
+
+impl DirectHttp {
+    pub const NAME: &'static str = "http";
+}
+
+#[async_trait]
+impl SourceResolver for DirectHttp {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn handles(&self, query: &str) -> bool {
+        let url = url_of(query);
+        if !url.starts_with("http") {
+            return false;
+        }
+
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        crate::library::is_audio(Path::new(path))
+    }
+
+    async fn resolve(&self, query: &str, known: Option<Metadata>) -> Result<Resolved> {
+        let url = url_of(query).to_string();
+        let (source, buffered) = lazy::ffmpeg(url.clone()).await?;
+
+        let input: Input = source.into();
+        let mut metadata = *input.metadata.clone();
+
+        // streams rarely have tags, the file name is better than nothing
+        metadata.title = known
+            .and_then(|known| known.title)
+            .or(metadata.title)
+            .or_else(|| {
+                let path = url.split(['?', '#']).next().unwrap_or_default();
+                path.rsplit('/').next().map(str::to_string)
+            });
+        metadata.source_url = Some(url.clone());
+
+        Ok(Resolved {
+            input,
+            metadata,
+            buffered,
+            song: Song::new(Self::NAME, url),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SourceError;
+
+    /// Handles queries starting with its prefix, and never finds anything.
+    struct Fake {
+        name: &'static str,
+        prefix: &'static str,
+    }
+
+    #[async_trait]
+    impl SourceResolver for Fake {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn handles(&self, query: &str) -> bool {
+            query.starts_with(self.prefix)
+        }
+
+        async fn resolve(&self, query: &str, _known: Option<Metadata>) -> Result<Resolved> {
+            Err(SourceError::NotFound(query.into()).into())
+        }
+    }
+
+    fn fakes() -> Resolvers {
+        Resolvers::new(vec![
+            Box::new(Fake {
+                name: "narrow",
+                prefix: "fake:narrow",
+            }),
+            Box::new(Fake {
+                name: "broad",
+                prefix: "fake:",
+            }),
+        ])
+    }
+
+    #[test]
+    fn first_resolver_handling_a_query_wins() {
+        let resolvers = fakes();
+
+        let name = |query| resolvers.for_query(query).map(|r| r.name());
+        assert_eq!(name("fake:narrow/song"), Some("narrow"));
+        assert_eq!(name("fake:other"), Some("broad"));
+        assert_eq!(name("something else"), None);
+    }
+
+    #[test]
+    fn resolvers_are_found_by_name() {
+        let resolvers = fakes();
+
+        assert_eq!(resolvers.get("broad").map(|r| r.name()), Some("broad"));
+        assert!(resolvers.get("missing").is_none());
+    }
+
+    #[test]
+    fn default_resolvers_pick_by_query() {
+        let resolvers = Resolvers::default();
+
+        let name = |query| resolvers.for_query(query).map(|r| r.name());
+        assert_eq!(
+            name("https://radio.example/live.mp3?x=1"),
+            Some(DirectHttp::NAME)
+        );
+        assert_eq!(name("https://youtu.be/abc"), Some(Ytdl::NAME));
+        assert_eq!(name("never gonna give you up"), Some(Search::NAME));
+        assert_eq!(name(""), None);
+    }
+
+    #[test]
+    fn songs_round_trip() {
+        let song = Song::new(Ytdl::NAME, "https://youtu.be/abc");
+        let json = serde_json::to_string(&song).unwrap();
+
+        assert_eq!(serde_json::from_str::<Song>(&json).unwrap(), song);
+    }
+}